@@ -0,0 +1,84 @@
+//! Compares the geometry shader and the instanced render path on the same
+//! line data. Needs an OpenGL context, run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use dear_gui::graphics::dyn_vertex_buffer::DynVertexBuffer;
+use dear_gui::graphics::primitives::{Color, Line};
+use dear_gui::graphics::programs::{FrameUniforms, Programs, RenderPath};
+use euclid::{Point2D, Transform2D};
+use glium::glutin::{dpi::PhysicalSize, event_loop::EventLoop, ContextBuilder};
+use glium::texture::Texture2d;
+use glium::{framebuffer::SimpleFrameBuffer, HeadlessRenderer, Surface};
+
+const SEGMENTS: usize = 100_000;
+const FRAMES: u32 = 100;
+
+fn main() {
+    let event_loop = EventLoop::new();
+    let context = ContextBuilder::new()
+        .build_headless(&event_loop, PhysicalSize::new(1024, 1024))
+        .unwrap();
+    let display = HeadlessRenderer::new(context).unwrap();
+    let texture = Texture2d::empty(&display, 1024, 1024).unwrap();
+    let programs = Programs::new(&display).unwrap();
+    programs.set_frame_uniforms(FrameUniforms::new(
+        Transform2D::identity(),
+        texture.dimensions(),
+        0.,
+        1.,
+    ));
+
+    // A deterministic pseudo random set of short segments.
+    let mut seed = 0x2545_f491_u32;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32 * 2. - 1.
+    };
+    let mut lines = DynVertexBuffer::<Line>::new(&display).unwrap();
+    lines
+        .extend_n(
+            &display,
+            SEGMENTS,
+            (0..SEGMENTS).map(|_| {
+                let start = Point2D::<f32, ()>::new(next(), next());
+                let end = start + euclid::vec2(next(), next()) * 0.05;
+                Line::from_points(start, end)
+            }),
+        )
+        .unwrap();
+
+    for &path in &[RenderPath::GeometryShader, RenderPath::Instanced] {
+        if !programs.line.set_path(path) {
+            println!("{:?}: not supported by this context", path);
+            continue;
+        }
+
+        let mut frame = SimpleFrameBuffer::new(&display, &texture).unwrap();
+        let mut total = Duration::from_secs(0);
+        for _ in 0..FRAMES {
+            frame.clear_color(1., 1., 1., 1.);
+            let start = Instant::now();
+            programs
+                .draw_lines(
+                    &mut frame,
+                    lines.get(),
+                    Color::BLUE,
+                    0.002,
+                    Transform2D::identity(),
+                )
+                .unwrap();
+            display.finish();
+            total += start.elapsed();
+        }
+
+        println!(
+            "{:?}: {} segments, {:?} per frame",
+            path,
+            SEGMENTS,
+            total / FRAMES
+        );
+    }
+}
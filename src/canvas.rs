@@ -1,20 +1,36 @@
 use std::any::Any;
 use std::cell::RefCell;
-
-use euclid::{Point2D, Size2D, Transform2D, Vector2D};
-use glium::glutin::event::{ElementState, Event, MouseButton, MouseScrollDelta, WindowEvent};
-use glium::{Display, DrawError, Frame};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use euclid::{Box2D, Point2D, Size2D, Transform2D, Vector2D};
+use glium::glutin::event::{
+    ElementState, Event, KeyboardInput, MouseButton, MouseScrollDelta, WindowEvent,
+};
+use glium::index::BufferCreationError as IndexBufferCreationError;
+use glium::vertex::BufferCreationError;
+use glium::{Display, DrawError, Rect, Surface};
 use log::debug;
 
 use crate::event_handling::{EventHandler, FnEventHandler};
+use crate::geometry::quadtree::touches;
 use crate::geometry::{CanvasSpace, ModelSpace, PixelSpace, ScreenSpace};
 
-use crate::graphics::programs::Programs;
-// use crate::state::{CommandRequest, State};
+use crate::graphics::batch::Batcher;
+use crate::graphics::primitives::{Color, ColoredLine, ColoredPoint, Line, LinePoint};
+use crate::graphics::programs::{FrameUniforms, Programs};
+use crate::graphics::target::RenderTarget;
+use crate::grid::pixels_per_unit;
+use crate::snap::{Snap, Snapper};
 
 #[derive(Debug, Clone)]
 pub enum CanvasError {
     DrawError(DrawError),
+    /// A vertex buffer couldn't be created or grown.
+    BufferError(BufferCreationError),
+    /// An index buffer couldn't be created.
+    IndexBufferError(IndexBufferCreationError),
     InvalidGenericType,
 }
 
@@ -24,8 +40,42 @@ impl From<DrawError> for CanvasError {
     }
 }
 
+impl From<BufferCreationError> for CanvasError {
+    fn from(err: BufferCreationError) -> Self {
+        CanvasError::BufferError(err)
+    }
+}
+
+impl From<IndexBufferCreationError> for CanvasError {
+    fn from(err: IndexBufferCreationError) -> Self {
+        CanvasError::IndexBufferError(err)
+    }
+}
+
 pub trait CanvasObject: Any + CanvasObjectExt {
     fn draw<'a>(&self, ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError>;
+
+    /// Extent of everything the object draws, before the model transform is
+    /// applied. `None` if it is unknown or unbounded.
+    fn bounds(&self) -> Option<Box2D<f32, ModelSpace>> {
+        None
+    }
+}
+
+/// Bounds of `obj` in canvas space, if it is drawn with `transform`.
+pub fn canvas_bounds(
+    obj: &dyn CanvasObject,
+    transform: &Transform2D<f32, ModelSpace, CanvasSpace>,
+) -> Option<Box2D<f32, CanvasSpace>> {
+    obj.bounds().map(|b| transform.outer_transformed_box(&b))
+}
+
+/// Smallest box containing all `bounds`, `None` if there are none.
+pub fn union_bounds<U>(bounds: impl IntoIterator<Item = Box2D<f32, U>>) -> Option<Box2D<f32, U>> {
+    bounds.into_iter().fold(None, |acc, b| match acc {
+        Some(acc) => Some(b.union(&acc)),
+        None => Some(b),
+    })
 }
 
 pub trait CanvasObjectExt: Any {
@@ -54,19 +104,53 @@ pub struct InputState {
 }
 
 pub struct Canvas {
+    /// Unique among the canvases of the process, for ImGui IDs.
+    id: usize,
     input: InputState,
     zoom: f32,
-    translation: Vector2D<f32, CanvasSpace>,
-    preview_translation: Option<Vector2D<f32, CanvasSpace>>,
+    /// In `f64`, so the view can be moved precisely far from the canvas
+    /// origin.
+    translation: Vector2D<f64, CanvasSpace>,
+    preview_translation: Option<Vector2D<f64, CanvasSpace>>,
+    /// Part of the window the canvas covers, the whole window if `None`.
+    viewport: Option<Box2D<u32, PixelSpace>>,
+    /// `viewport` divided by the window size, so it can follow resizes.
+    relative_viewport: Option<Box2D<f32, PixelSpace>>,
+    window_size: Size2D<u32, PixelSpace>,
     dimensions: Size2D<u32, PixelSpace>,
-    programs: Programs,
-    click_handler: Box<dyn FnMut(Point2D<f32, CanvasSpace>, MouseButton)>,
+    scale_factor: f64,
+    created: Instant,
+    /// Shared with the canvases made with [`Canvas::new_sharing`]. The origin
+    /// and the frame uniforms are set again at the start of every draw.
+    programs: Rc<Programs>,
+    batcher: Rc<RefCell<Batcher>>,
+    snapper: Snapper,
+    /// Where the cursor snaps to right now, shown while drawing.
+    cursor_snap: Option<Snap>,
+    click_handler: Box<dyn FnMut(Point2D<f64, CanvasSpace>, MouseButton)>,
 }
 
 impl Canvas {
     pub fn new(display: &Display) -> Canvas {
-        let programs = Programs::new(display).unwrap();
+        let programs = Rc::new(Programs::new(display).unwrap());
+        let batcher = Rc::new(RefCell::new(Batcher::new(display)));
+        Canvas::with_renderer(display, programs, batcher)
+    }
+
+    /// A new canvas which shares the compiled programs and the batcher with
+    /// `other`, e.g. another view into the same window.
+    pub fn new_sharing(display: &Display, other: &Canvas) -> Canvas {
+        Canvas::with_renderer(display, other.programs.clone(), other.batcher.clone())
+    }
+
+    fn with_renderer(
+        display: &Display,
+        programs: Rc<Programs>,
+        batcher: Rc<RefCell<Batcher>>,
+    ) -> Canvas {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
         Canvas {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             input: InputState {
                 mouse_pos: Point2D::new(0., 0.),
                 left_pressed_start: None,
@@ -75,53 +159,320 @@ impl Canvas {
             zoom: 1.0,
             translation: Vector2D::new(0., 0.),
             preview_translation: None,
+            viewport: None,
+            relative_viewport: None,
+            window_size: display.get_framebuffer_dimensions().into(),
             dimensions: display.get_framebuffer_dimensions().into(),
+            scale_factor: display.gl_window().window().scale_factor(),
+            created: Instant::now(),
             programs,
+            batcher,
+            snapper: Snapper::default(),
+            cursor_snap: None,
             click_handler: Box::new(|_, _| ()),
         }
     }
 
+    /// Maps window pixels to the screen space of this canvas, taking the
+    /// viewport into account.
     pub fn pixel_transform(&self) -> Transform2D<f32, PixelSpace, ScreenSpace> {
+        let origin = self.viewport.map(|v| v.min).unwrap_or_default().to_f32();
         let w = self.dimensions.width as f32 / 2.;
         let h = self.dimensions.height as f32 / 2.;
-        Transform2D::translation(-w, -h).then_scale(1. / w, -1. / h)
+        Transform2D::translation(-origin.x - w, -origin.y - h).then_scale(1. / w, -1. / h)
+    }
+
+    /// Restricts the canvas to a part of the window, given in window pixels
+    /// from the top left. With `None` it covers the whole window. When the
+    /// window is resized the viewport keeps its place relative to it.
+    ///
+    /// Several canvases with their own viewports can share a window, see
+    /// [`AppInit::add_view`](crate::AppInit::add_view).
+    pub fn set_viewport(&mut self, viewport: Option<Box2D<u32, PixelSpace>>) {
+        let window = self.window_size.max(Size2D::new(1, 1)).to_f32();
+        self.relative_viewport =
+            viewport.map(|v| v.to_f32().scale(1. / window.width, 1. / window.height));
+        self.apply_viewport(viewport);
+    }
+
+    fn apply_viewport(&mut self, viewport: Option<Box2D<u32, PixelSpace>>) {
+        self.viewport = viewport;
+        self.dimensions = viewport.map(|v| v.size()).unwrap_or(self.window_size);
+        self.cursor_snap = None;
+    }
+
+    pub fn viewport(&self) -> Option<Box2D<u32, PixelSpace>> {
+        self.viewport
+    }
+
+    /// Whether a window position lies inside the viewport of this canvas.
+    pub fn contains(&self, pixel: Point2D<f32, PixelSpace>) -> bool {
+        match self.viewport {
+            Some(viewport) => viewport.to_f32().contains(pixel),
+            None => true,
+        }
+    }
+
+    fn gl_viewport(&self) -> Option<Rect> {
+        self.viewport.map(|v| Rect {
+            left: v.min.x,
+            bottom: self.window_size.height.saturating_sub(v.max.y),
+            width: v.width(),
+            height: v.height(),
+        })
     }
 
     pub fn view_transform(&self) -> Transform2D<f32, CanvasSpace, ScreenSpace> {
-        let tx = self.preview_translation.unwrap_or(self.translation);
-        let zm = self.zoom;
+        self.view_transform_f64().cast()
+    }
+
+    /// [`view_transform`](Canvas::view_transform) in `f64`, for canvas
+    /// positions beyond the precision of `f32`.
+    pub fn view_transform_f64(&self) -> Transform2D<f64, CanvasSpace, ScreenSpace> {
+        self.view_transform_from(Point2D::origin())
+    }
+
+    /// View transform for canvas coordinates relative to `origin`, computed
+    /// in `f64` so it stays precise where `origin` is close to the camera.
+    fn view_transform_from(
+        &self,
+        origin: Point2D<f64, CanvasSpace>,
+    ) -> Transform2D<f64, CanvasSpace, ScreenSpace> {
+        let tx = self.preview_translation.unwrap_or(self.translation) + origin.to_vector();
+        let zm = self.zoom as f64;
 
         Transform2D::translation(tx.x, tx.y).then_scale(
-            zm / self.dimensions.width as f32,
-            zm / self.dimensions.height as f32,
+            zm / self.dimensions.width as f64,
+            zm / self.dimensions.height as f64,
         )
     }
 
-    pub fn draw<T: Any + 'static>(
+    /// Canvas point the current frame is drawn relative to, see
+    /// [`Programs::set_origin`]. It is the camera position rounded to a
+    /// power of two about the size of the view, so it only changes when the
+    /// view moves by about its size and is exact in `f32` wherever that can
+    /// be.
+    pub fn render_origin(&self) -> Point2D<f64, CanvasSpace> {
+        let center = -self.preview_translation.unwrap_or(self.translation);
+        let size = 2. * self.dimensions.width.max(self.dimensions.height) as f64 / self.zoom as f64;
+        round_origin(center.to_point(), size)
+    }
+
+    pub fn draw<'t, T: Any + 'static>(
         &self,
-        target: &mut Frame,
+        target: impl Into<RenderTarget<'t>>,
         obj: &dyn CanvasObject,
         generic: &T,
     ) -> Result<(), CanvasError> {
-        let view_transform = self.view_transform();
+        let mut target = target.into();
+        // The viewport is in window pixels, a texture is always drawn whole.
+        if let (Some(viewport), true) = (self.gl_viewport(), target.is_frame()) {
+            target = target.with_viewport(viewport);
+        }
+        let origin = self.render_origin();
+        let view = View {
+            transform: self.view_transform(),
+            origin,
+            relative: self.view_transform_from(origin).cast(),
+        };
+        self.draw_view(target, view, obj, generic, self.cursor_snap)
+    }
+
+    /// Draws with another view than the one of the canvas, e.g. an overview
+    /// into a texture. The canvas state is not changed.
+    pub fn draw_with_view_transform<'t, T: Any + 'static>(
+        &self,
+        target: impl Into<RenderTarget<'t>>,
+        view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+        obj: &dyn CanvasObject,
+        generic: &T,
+    ) -> Result<(), CanvasError> {
+        let view = View {
+            transform: view_transform,
+            origin: Point2D::origin(),
+            relative: view_transform,
+        };
+        self.draw_view(target.into(), view, obj, generic, None)
+    }
+
+    fn draw_view<T: Any + 'static>(
+        &self,
+        target: RenderTarget,
+        view: View,
+        obj: &dyn CanvasObject,
+        generic: &T,
+        snap: Option<Snap>,
+    ) -> Result<(), CanvasError> {
+        let dimensions = target.get_dimensions();
+        let view_transform = view.transform;
+
+        self.programs.set_origin(view.origin);
+        self.batcher.borrow_mut().set_origin(view.origin);
+        self.programs.set_frame_uniforms(FrameUniforms::new(
+            view.relative,
+            dimensions,
+            self.created.elapsed().as_secs_f32(),
+            self.scale_factor as f32,
+        ));
 
         let mut ctx = DrawingContext {
             generic,
             programs: &self.programs,
+            batcher: &self.batcher,
             target,
             view_transform,
+            origin: view.origin,
             model_transform: Transform2D::identity(),
-            dimensions: self.dimensions.into(),
+            dimensions,
         };
 
-        obj.draw(&mut ctx)
+        // Objects entirely outside of the view are skipped.
+        let culled = obj.bounds().is_some_and(|b| !ctx.is_visible(&b));
+        if !culled {
+            if let Err(err) = obj.draw(&mut ctx) {
+                // Don't leave what was batched before the error for the next view.
+                self.batcher.borrow_mut().clear();
+                return Err(err);
+            }
+        }
+
+        if let Some(snap) = snap {
+            let pixels_per_unit = pixels_per_unit(view_transform, dimensions.0);
+            let indicator = Snapper::indicator(snap, 12., pixels_per_unit);
+            // One pixel wide, widths are in canvas units.
+            self.batcher.borrow_mut().add_lines(
+                &indicator,
+                self.snapper.color,
+                1. / pixels_per_unit,
+                1.,
+                Transform2D::identity(),
+            );
+        }
+
+        self.batcher
+            .borrow_mut()
+            .flush(&self.programs, &mut ctx.target)?;
+        Ok(())
+    }
+
+    /// Tells canvases apart, e.g. in the IDs of their ImGui windows.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn dimensions(&self) -> Size2D<u32, PixelSpace> {
+        self.dimensions
+    }
+
+    /// Part of the canvas that is currently visible.
+    pub fn visible_rect(&self) -> Box2D<f32, CanvasSpace> {
+        self.visible_rect_f64().to_f32()
+    }
+
+    /// [`visible_rect`](Canvas::visible_rect) in `f64`.
+    pub fn visible_rect_f64(&self) -> Box2D<f64, CanvasSpace> {
+        visible_rect(self.view_transform_f64())
+    }
+
+    pub fn translation(&self) -> Vector2D<f64, CanvasSpace> {
+        self.translation
+    }
+
+    pub fn set_translation(&mut self, translation: Vector2D<f64, CanvasSpace>) {
+        self.translation = translation;
+    }
+
+    /// Zooms and moves the view so `bounds` fill the canvas, leaving a
+    /// `margin` in pixels around them.
+    pub fn fit(&mut self, bounds: Box2D<f32, CanvasSpace>, margin: f32) {
+        self.fit_f64(bounds.to_f64(), margin);
+    }
+
+    /// [`fit`](Canvas::fit) for bounds in `f64`.
+    pub fn fit_f64(&mut self, bounds: Box2D<f64, CanvasSpace>, margin: f32) {
+        let width = (self.dimensions.width as f64 - 2. * margin as f64).max(1.);
+        let height = (self.dimensions.height as f64 - 2. * margin as f64).max(1.);
+        let size = bounds.size();
+        let zoom = (2. * width / size.width).min(2. * height / size.height) as f32;
+        // A point or a line has no size to fit, keep the zoom then.
+        if zoom.is_finite() {
+            self.zoom = zoom;
+        }
+        self.center_on_f64(bounds.center());
+    }
+
+    /// Moves the view so `point` is in the middle of the screen.
+    pub fn center_on(&mut self, point: Point2D<f32, CanvasSpace>) {
+        self.center_on_f64(point.to_f64());
+    }
+
+    /// [`center_on`](Canvas::center_on) for a point in `f64`.
+    pub fn center_on_f64(&mut self, point: Point2D<f64, CanvasSpace>) {
+        self.translation = -point.to_vector();
+    }
+
+    /// Access to the programs, e.g. to select their render path. They are
+    /// shared with the canvases made with [`Canvas::new_sharing`].
+    pub fn programs(&self) -> &Programs {
+        &self.programs
+    }
+
+    pub fn snapper(&self) -> &Snapper {
+        &self.snapper
+    }
+
+    /// Register snap targets or change the snapping settings here.
+    pub fn snapper_mut(&mut self) -> &mut Snapper {
+        &mut self.snapper
+    }
+
+    /// Position under the cursor, snapped if snapping is enabled. Use this
+    /// while dragging things around the canvas.
+    pub fn cursor_position(&self) -> Point2D<f32, CanvasSpace> {
+        self.cursor_position_f64().to_f32()
+    }
+
+    /// [`cursor_position`](Canvas::cursor_position) in `f64`, the position
+    /// isn't rounded to `f32` unless it snaps.
+    pub fn cursor_position_f64(&self) -> Point2D<f64, CanvasSpace> {
+        self.snap(self.to_canvas(self.input.mouse_pos))
+    }
+
+    fn to_canvas(&self, pixel: Point2D<f32, PixelSpace>) -> Point2D<f64, CanvasSpace> {
+        let screen = self.pixel_transform().transform_point(pixel);
+        self.view_transform_f64()
+            .inverse()
+            .unwrap()
+            .transform_point(screen.to_f64())
+    }
+
+    fn find_snap(&self, position: Point2D<f32, CanvasSpace>) -> Option<Snap> {
+        let pixels_per_unit = pixels_per_unit(self.view_transform(), self.dimensions.width);
+        self.snapper.snap(position, pixels_per_unit)
+    }
+
+    fn snap(&self, position: Point2D<f64, CanvasSpace>) -> Point2D<f64, CanvasSpace> {
+        self.find_snap(position.to_f32())
+            .map(|snap| snap.position.to_f64())
+            .unwrap_or(position)
+    }
+
+    fn update_cursor_snap(&mut self) {
+        self.cursor_snap = self.find_snap(self.to_canvas(self.input.mouse_pos).to_f32());
     }
 
     pub fn input(&mut self) -> CanvasInput<'_> {
         CanvasInput(RefCell::new(self))
     }
 
-    pub fn set_click_handler(&mut self, handler: Box<dyn FnMut(Point2D<f32, CanvasSpace>, MouseButton)>) {
+    /// Tells the canvas that the cursor moved to `position` over another
+    /// canvas drawn on top of it, so it stops showing where it would snap.
+    pub fn cover_cursor(&mut self, position: Point2D<f32, PixelSpace>) {
+        self.input.mouse_pos = position;
+        self.cursor_snap = None;
+    }
+
+    pub fn set_click_handler(&mut self, handler: Box<dyn FnMut(Point2D<f64, CanvasSpace>, MouseButton)>) {
         self.click_handler = handler;
     }
 }
@@ -131,6 +482,15 @@ impl Canvas {
 /// captured input and once for usual input.
 pub struct CanvasInput<'a>(RefCell<&'a mut Canvas>);
 impl<'a> CanvasInput<'a> {
+    pub fn contains(&self, pixel: Point2D<f32, PixelSpace>) -> bool {
+        self.0.borrow().contains(pixel)
+    }
+
+    /// See [`Canvas::cover_cursor`].
+    pub fn cover_cursor(&self, position: Point2D<f32, PixelSpace>) {
+        self.0.borrow_mut().cover_cursor(position);
+    }
+
     pub fn capture(&'a self) -> impl EventHandler + 'a {
         FnEventHandler(move |event| {
             let event = if let Event::WindowEvent { event, .. } = event {
@@ -154,12 +514,7 @@ impl<'a> CanvasInput<'a> {
                     if let Some(start) = canvas.input.left_pressed_start {
                         let delta = canvas.input.mouse_pos - start;
                         if delta.length() < 3. {
-                            let screen = canvas.pixel_transform().transform_point(start);
-                            let click_pos = canvas
-                                .view_transform()
-                                .inverse()
-                                .unwrap()
-                                .transform_point(screen);
+                            let click_pos = canvas.snap(canvas.to_canvas(start));
                             debug!("Clicked at: {:?}", click_pos);
                             (canvas.click_handler)(click_pos, MouseButton::Left);
                         } else {
@@ -169,11 +524,12 @@ impl<'a> CanvasInput<'a> {
                                 .inverse()
                                 .unwrap()
                                 .transform_vector(screen);
-                            canvas.translation += model;
+                            canvas.translation += model.to_f64();
                         }
                     }
                     canvas.input.left_pressed_start = None;
                     canvas.preview_translation = None;
+                    canvas.update_cursor_snap();
                     // Don't capture this event so Imgui knows the LMB is released.
                     false
                 }
@@ -191,9 +547,10 @@ impl<'a> CanvasInput<'a> {
                                 .inverse()
                                 .unwrap()
                                 .transform_vector(screen);
-                            Some(canvas.translation + model)
+                            Some(canvas.translation + model.to_f64())
                         };
                     }
+                    canvas.update_cursor_snap();
 
                     true
                 }
@@ -210,20 +567,22 @@ impl<'a> CanvasInput<'a> {
             };
 
             let mut canvas = self.0.borrow_mut();
+            let hovered = canvas.contains(canvas.input.mouse_pos);
 
             match event {
                 WindowEvent::MouseWheel {
                     delta: MouseScrollDelta::LineDelta(_x, y),
                     ..
-                } => {
+                } if hovered => {
                     canvas.zoom *= 1. + (y / 10.);
+                    canvas.update_cursor_snap();
                     true
                 }
                 WindowEvent::MouseInput {
                     button: MouseButton::Left,
                     state: ElementState::Pressed,
                     ..
-                } => {
+                } if hovered => {
                     canvas.input.left_pressed_start = Some(canvas.input.mouse_pos);
                     true
                 }
@@ -231,7 +590,7 @@ impl<'a> CanvasInput<'a> {
                     button: MouseButton::Right,
                     state: ElementState::Pressed,
                     ..
-                } => {
+                } if hovered => {
                     canvas.input.right_pressed_start = Some(canvas.input.mouse_pos);
                     true
                 }
@@ -239,15 +598,10 @@ impl<'a> CanvasInput<'a> {
                     button: MouseButton::Right,
                     state: ElementState::Released,
                     ..
-                } => {
+                } if canvas.input.right_pressed_start.is_some() => {
                     if let Some(rps) = canvas.input.right_pressed_start.take() {
                         if rps.distance_to(canvas.input.mouse_pos) < 3. {
-                            let screen = canvas.pixel_transform().transform_point(rps);
-                            let click_pos = canvas
-                                .view_transform()
-                                .inverse()
-                                .unwrap()
-                                .transform_point(screen);
+                            let click_pos = canvas.snap(canvas.to_canvas(rps));
                             (canvas.click_handler)(click_pos, MouseButton::Right);
                         }
                     }
@@ -256,10 +610,42 @@ impl<'a> CanvasInput<'a> {
                 WindowEvent::CursorMoved { position, .. } => {
                     let position = Point2D::new(position.x as f32, position.y as f32);
                     canvas.input.mouse_pos = position;
+                    if canvas.contains(position) {
+                        canvas.update_cursor_snap();
+                        true
+                    } else {
+                        // Let canvases in other viewports see the cursor.
+                        canvas.cursor_snap = None;
+                        false
+                    }
+                }
+                WindowEvent::CursorLeft { .. } => {
+                    canvas.cursor_snap = None;
+                    false
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } if hovered && *key == canvas.snapper.toggle_key => {
+                    canvas.snapper.enabled = !canvas.snapper.enabled;
+                    canvas.update_cursor_snap();
                     true
                 }
                 WindowEvent::Resized(size) => {
-                    canvas.dimensions = Size2D::new(size.width, size.height);
+                    canvas.window_size = Size2D::new(size.width, size.height);
+                    let viewport = canvas
+                        .relative_viewport
+                        .map(|v| scale_viewport(v, canvas.window_size));
+                    canvas.apply_viewport(viewport);
+                    false
+                }
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    canvas.scale_factor = *scale_factor;
                     false
                 }
                 _ => false,
@@ -268,27 +654,59 @@ impl<'a> CanvasInput<'a> {
     }
 }
 
+/// Pixels of a viewport given relative to a window of `window_size`.
+fn scale_viewport(
+    relative: Box2D<f32, PixelSpace>,
+    window_size: Size2D<u32, PixelSpace>,
+) -> Box2D<u32, PixelSpace> {
+    let window = window_size.to_f32();
+    relative.scale(window.width, window.height).round().to_u32()
+}
+
+/// A view transform with the origin it is drawn relative to.
+struct View {
+    transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+    origin: Point2D<f64, CanvasSpace>,
+    /// `transform` for canvas coordinates relative to `origin`.
+    relative: Transform2D<f32, CanvasSpace, ScreenSpace>,
+}
+
 pub struct DrawingContext<'a> {
     pub programs: &'a Programs,
-    pub target: &'a mut Frame,
+    /// Primitives added here are drawn before the next direct draw into
+    /// [`target`](DrawingContext::target), or at the end of [`Canvas::draw`],
+    /// so they keep their place in the draw order.
+    pub batcher: &'a RefCell<Batcher>,
+    target: RenderTarget<'a>,
     pub generic: &'a (dyn Any + 'static),
-    pub model_transform: Transform2D<f32, ModelSpace, CanvasSpace>,
+    /// In `f64`, so models placed far from the origin of the canvas stay
+    /// precise until the render origin is subtracted.
+    pub model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
     pub view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+    /// Canvas point the frame is drawn relative to, see
+    /// [`Canvas::render_origin`].
+    pub origin: Point2D<f64, CanvasSpace>,
     pub dimensions: (u32, u32),
 }
 
 impl<'a> DrawingContext<'a> {
+    /// Calls `f` with `transform` applied on top of the current model
+    /// transform, so nested calls compose.
     pub fn with_model_transform<T>(
         &mut self,
-        transform: Transform2D<f32, ModelSpace, CanvasSpace>,
+        transform: Transform2D<f64, ModelSpace, CanvasSpace>,
         f: impl FnOnce(DrawingContext) -> T,
     ) -> T {
         let new = DrawingContext {
             generic: self.generic,
-            model_transform: transform,
+            model_transform: transform
+                .with_destination::<ModelSpace>()
+                .then(&self.model_transform),
             view_transform: self.view_transform,
+            origin: self.origin,
             programs: self.programs,
-            target: self.target,
+            batcher: self.batcher,
+            target: self.target.reborrow(),
             dimensions: self.dimensions,
         };
 
@@ -300,17 +718,203 @@ impl<'a> DrawingContext<'a> {
             generic,
             model_transform: self.model_transform,
             view_transform: self.view_transform,
+            origin: self.origin,
             programs: self.programs,
-            target: self.target,
+            batcher: self.batcher,
+            target: self.target.reborrow(),
             dimensions: self.dimensions,
         };
 
         f(new)
     }
 
+    /// The target for drawing through `programs` directly. Everything
+    /// batched so far is drawn first, so it stays below what is drawn next.
+    pub fn target(&mut self) -> Result<&mut RenderTarget<'a>, CanvasError> {
+        self.batcher
+            .borrow_mut()
+            .flush(self.programs, &mut self.target)?;
+        Ok(&mut self.target)
+    }
+
+    /// Calls `f` with everything it draws faded by `opacity`, on top of the
+    /// current opacity.
+    pub fn with_opacity<T>(&mut self, opacity: f32, f: impl FnOnce(&mut DrawingContext) -> T) -> T {
+        let previous = self.programs.opacity();
+        self.programs.set_opacity(previous * opacity);
+        let result = f(self);
+        self.programs.set_opacity(previous);
+        result
+    }
+
+    pub fn batch_line_strip(&self, strip: &[LinePoint], color: Color, width: f32) {
+        self.batcher.borrow_mut().add_line_strip(
+            strip,
+            color,
+            width,
+            self.programs.opacity(),
+            self.model_transform,
+        );
+    }
+
+    pub fn batch_lines(&self, lines: &[Line], color: Color, width: f32) {
+        self.batcher.borrow_mut().add_lines(
+            lines,
+            color,
+            width,
+            self.programs.opacity(),
+            self.model_transform,
+        );
+    }
+
+    pub fn batch_colored_lines(&self, lines: &[ColoredLine], width: f32) {
+        self.batcher.borrow_mut().add_colored_lines(
+            lines,
+            width,
+            self.programs.opacity(),
+            self.model_transform,
+        );
+    }
+
+    pub fn batch_colored_points(&self, points: &[ColoredPoint], width: f32) {
+        self.batcher.borrow_mut().add_colored_points(
+            points,
+            width,
+            self.programs.opacity(),
+            self.model_transform,
+        );
+    }
+
+    /// Part of the canvas covered by the target, objects outside of it can be
+    /// skipped.
+    pub fn visible_rect(&self) -> Box2D<f32, CanvasSpace> {
+        visible_rect(self.view_transform.cast()).to_f32()
+    }
+
+    /// The visible part of the canvas in the current model space, `None` if
+    /// the model transform can't be inverted.
+    pub fn model_visible_rect(&self) -> Option<Box2D<f32, ModelSpace>> {
+        let inverse = self.model_transform.inverse()?;
+        Some(
+            inverse
+                .outer_transformed_box(&self.visible_rect().to_f64())
+                .to_f32(),
+        )
+    }
+
+    /// Whether something within `bounds` in the current model space can show
+    /// up in the target.
+    pub fn is_visible(&self, bounds: &Box2D<f32, ModelSpace>) -> bool {
+        let bounds = self.model_transform.outer_transformed_box(&bounds.to_f64());
+        touches(&bounds.to_f32(), &self.visible_rect())
+    }
+
+    /// Pixels per model space unit, to turn tolerances in pixels into model
+    /// units. Non uniform scales are averaged.
+    pub fn pixels_per_unit(&self) -> f32 {
+        let t = self.model_transform;
+        let scale = (t.m11 * t.m22 - t.m12 * t.m21).abs().sqrt();
+        pixels_per_unit(self.view_transform, self.dimensions.0) * scale as f32
+    }
+
+    /// The render origin in model space. Geometry in `f64` is converted
+    /// relative to it, e.g. with
+    /// [`LinePoint::from_relative`](crate::graphics::primitives::LinePoint::from_relative),
+    /// and drawn within
+    /// `with_model_transform(Transform2D::translation(origin.x, origin.y), ..)`.
+    pub fn model_origin(&self) -> Point2D<f64, ModelSpace> {
+        self.model_transform
+            .inverse()
+            .map(|inverse| inverse.transform_point(self.origin))
+            .unwrap_or_default()
+    }
+
     pub fn get_generic<T: 'static>(&self) -> Result<&'a T, CanvasError> {
         self.generic
             .downcast_ref()
             .ok_or(CanvasError::InvalidGenericType)
     }
 }
+
+/// `center` rounded to a multiple of the power of two at or above `size`.
+fn round_origin(center: Point2D<f64, CanvasSpace>, size: f64) -> Point2D<f64, CanvasSpace> {
+    let cell = 2_f64.powf(size.log2().ceil());
+    if !cell.is_normal() {
+        return Point2D::origin();
+    }
+    (center / cell).round() * cell
+}
+
+fn visible_rect(
+    view_transform: Transform2D<f64, CanvasSpace, ScreenSpace>,
+) -> Box2D<f64, CanvasSpace> {
+    let screen_to_canvas = view_transform.inverse().unwrap();
+    Box2D::from_points([
+        screen_to_canvas.transform_point(Point2D::new(-1., -1.)),
+        screen_to_canvas.transform_point(Point2D::new(1., 1.)),
+    ])
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn it_rounds_the_render_origin() {
+        let origin = round_origin(Point2D::new(1000.3, -700.), 100.);
+        assert_eq!(origin, Point2D::new(1024., -640.));
+        assert_eq!(round_origin(Point2D::new(1e20, 5.), 128.).y, 0.);
+        assert_eq!(round_origin(Point2D::new(3., 5.), 0.), Point2D::origin());
+        assert_eq!(
+            round_origin(Point2D::new(3., 5.), f64::NAN),
+            Point2D::origin()
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_render_origin_while_panning() {
+        let (size, cell) = (100., 128.);
+        let mut origin = round_origin(Point2D::origin(), size);
+        let mut changes = 0;
+        for step in 0..=1000 {
+            let center = Point2D::new(1e9 + step as f64, -0.5 * step as f64);
+            let next = round_origin(center, size);
+            assert!((center - next).abs().x <= cell / 2. && (center - next).abs().y <= cell / 2.);
+            if next != origin {
+                changes += 1;
+                origin = next;
+            }
+        }
+        // Once to get there, then once per cell panned along each axis.
+        assert!(
+            changes <= 1 + (1000 / 128 + 1) + (500 / 128 + 1),
+            "{}",
+            changes
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_visible_rect_precise_far_away() {
+        // A view 2 units wide around a point `f32` can't tell from 1e8.
+        let transform = Transform2D::translation(-1e8 - 0.25, 0.);
+        let rect = visible_rect(transform);
+        assert_eq!(rect.center(), Point2D::new(1e8 + 0.25, 0.));
+        assert_eq!(rect.width(), 2.);
+    }
+
+    #[test]
+    fn it_scales_viewports_with_the_window() {
+        let viewport = Box2D::new(Point2D::new(100., 50.), Point2D::new(400., 300.));
+        let relative = viewport.scale(1. / 800., 1. / 600.);
+        assert_eq!(
+            scale_viewport(relative, Size2D::new(800, 600)),
+            Box2D::new(Point2D::new(100, 50), Point2D::new(400, 300))
+        );
+        assert_eq!(
+            scale_viewport(relative, Size2D::new(1600, 300)),
+            Box2D::new(Point2D::new(200, 25), Point2D::new(800, 150))
+        );
+        assert!(scale_viewport(relative, Size2D::new(0, 0)).is_empty());
+    }
+}
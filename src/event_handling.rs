@@ -1,4 +1,7 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+};
 
 use glium::glutin::event::{Event, KeyboardInput, VirtualKeyCode, WindowEvent};
 use glium::Display;
@@ -26,6 +29,16 @@ where
     }
 }
 
+/// Offers the event to every handler in order until one handles it.
+impl<T> EventHandler for Vec<T>
+where
+    T: EventHandler,
+{
+    fn handle_event(&mut self, event: &Event<()>) -> bool {
+        self.iter_mut().any(|handler| handler.handle_event(event))
+    }
+}
+
 pub struct FnEventHandler<T: FnMut(&Event<()>) -> bool>(pub T);
 impl<T> EventHandler for FnEventHandler<T>
 where
@@ -49,6 +62,7 @@ pub struct Imgui {
     pub platform: WinitPlatform,
     pub renderer: Renderer,
     pub display: Display,
+    mouse_passthrough: Vec<Weak<Cell<bool>>>,
 }
 
 impl Imgui {
@@ -82,13 +96,30 @@ impl Imgui {
             platform,
             renderer,
             display,
+            mouse_passthrough: Vec::new(),
         };
         Rc::new(RefCell::new(imgui))
     }
+
+    /// Mouse events are not captured by the UI while `flag` is set, so they
+    /// reach the handlers after it. Widgets showing a canvas set it while
+    /// they are hovered. Only a weak reference is kept, the flag stops
+    /// counting once its owner is dropped.
+    pub fn pass_mouse_through(&mut self, flag: &Rc<Cell<bool>>) {
+        self.mouse_passthrough.push(Rc::downgrade(flag));
+    }
 }
 
 impl EventHandler for Imgui {
     fn handle_event(&mut self, event: &Event<()>) -> bool {
+        self.mouse_passthrough
+            .retain(|flag| flag.strong_count() > 0);
+        let passthrough = self
+            .mouse_passthrough
+            .iter()
+            .filter_map(Weak::upgrade)
+            .any(|flag| flag.get());
+        let want_capture_mouse = self.context.io().want_capture_mouse && !passthrough;
         let io = self.context.io_mut();
         let gl_win = self.display.gl_window();
         let window = gl_win.window();
@@ -121,9 +152,9 @@ impl EventHandler for Imgui {
                     _ => false,
                 },
                 WindowEvent::ReceivedCharacter(_) => io.want_capture_keyboard,
-                WindowEvent::CursorMoved { .. } => io.want_capture_mouse,
-                WindowEvent::MouseWheel { .. } => io.want_capture_mouse,
-                WindowEvent::MouseInput { .. } => io.want_capture_mouse,
+                WindowEvent::CursorMoved { .. } => want_capture_mouse,
+                WindowEvent::MouseWheel { .. } => want_capture_mouse,
+                WindowEvent::MouseInput { .. } => want_capture_mouse,
                 _ => false,
             },
             _ => false,
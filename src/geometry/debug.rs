@@ -1,9 +1,10 @@
 use std::time::{Duration, Instant};
 
+use euclid::Box2D;
 use glium::Display;
 
-use crate::canvas::{CanvasError, CanvasObject, DrawingContext};
-use crate::geometry::{CanvasSpace, Line};
+use crate::canvas::{union_bounds, CanvasError, CanvasObject, DrawingContext};
+use crate::geometry::{CanvasSpace, Line, ModelSpace};
 use crate::graphics::dyn_vertex_buffer::DynVertexBuffer;
 use crate::graphics::primitives::{Color, Line as GLLine};
 
@@ -34,8 +35,7 @@ impl DebugGeometry {
                 len,
                 self.lines
                     .iter()
-                    .map(|(_, ls)| ls)
-                    .flatten()
+                    .flat_map(|(_, ls)| ls)
                     .cloned()
                     .map(GLLine::from_line),
             )
@@ -49,15 +49,24 @@ impl DebugGeometry {
 
 impl CanvasObject for DebugGeometry {
     fn draw<'a>(&self, ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+        let model_transform = ctx.model_transform;
         ctx.programs.draw_lines(
-            ctx.target,
+            ctx.target()?,
             self.line_buffer.get(),
             Color::BLUE,
             1.,
-            ctx.model_transform,
-            ctx.view_transform,
+            model_transform,
         )?;
 
         Ok(())
     }
+
+    fn bounds(&self) -> Option<Box2D<f32, ModelSpace>> {
+        let points = self
+            .lines
+            .iter()
+            .flat_map(|(_, ls)| ls)
+            .flat_map(|l| vec![l.start, l.end]);
+        union_bounds(points.map(|p| Box2D::new(p, p).cast_unit()))
+    }
 }
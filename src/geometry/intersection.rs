@@ -0,0 +1,504 @@
+//! Intersections between segments, circles and polygons.
+//!
+//! Comparisons use a tolerance relative to the size of the shapes, so
+//! touching shapes intersect despite rounding, in `f32` as well as `f64`.
+
+use std::cmp::Ordering;
+
+use euclid::{Point2D, Vector2D};
+
+use super::{Circle, Line, Scalar};
+
+/// Where two segments meet.
+#[derive(Clone, Debug)]
+pub enum SegmentIntersection<S, F = f32> {
+    Point(Point2D<F, S>),
+    /// The segments are collinear and share this part, which runs in the
+    /// direction of the first segment.
+    Overlap(Line<S, F>),
+}
+
+impl<S, F: Scalar> Line<S, F> {
+    /// Where the segments cross, touch or overlap. Unlike
+    /// [`intersect`](Line::intersect) this handles parallel segments and
+    /// segments of zero length.
+    pub fn intersect_segment(&self, other: &Line<S, F>) -> Option<SegmentIntersection<S, F>> {
+        let d1 = self.end - self.start;
+        let d2 = other.end - other.start;
+        let (length1, length2) = (d1.length(), d2.length());
+        let tolerance = relative_tolerance::<F>() * length1.max(length2);
+
+        if length1 <= tolerance || length2 <= tolerance {
+            let (point, segment) = if length1 <= length2 {
+                (self.midpoint(), other)
+            } else {
+                (other.midpoint(), self)
+            };
+            return if segment.distance_to(point) <= tolerance.max(F::min_positive_value()) {
+                Some(SegmentIntersection::Point(point))
+            } else {
+                None
+            };
+        }
+
+        let v = other.start - self.start;
+        let denom = d1.cross(d2);
+        if denom.abs() <= relative_tolerance::<F>() * length1 * length2 {
+            if v.cross(d1).abs() > tolerance * length1 {
+                return None;
+            }
+
+            // Collinear, clip the other segment to this one along it.
+            let length_squared = d1.square_length();
+            let t0 = v.dot(d1) / length_squared;
+            let t1 = (other.end - self.start).dot(d1) / length_squared;
+            let start = t0.min(t1).max(F::zero());
+            let end = t0.max(t1).min(F::one());
+            let t_tolerance = tolerance / length1;
+            return if end < start - t_tolerance {
+                None
+            } else if end - start <= t_tolerance {
+                let t = (start + end) / F::of(2.);
+                Some(SegmentIntersection::Point(self.start + d1 * t))
+            } else {
+                Some(SegmentIntersection::Overlap(Line {
+                    start: self.start + d1 * start,
+                    end: self.start + d1 * end,
+                }))
+            };
+        }
+
+        let t1 = v.cross(d2) / denom;
+        let t2 = v.cross(d1) / denom;
+        let on_segment = |t: F, length: F| {
+            let slack = tolerance / length;
+            -slack <= t && t <= F::one() + slack
+        };
+        if on_segment(t1, length1) && on_segment(t2, length2) {
+            let t = t1.max(F::zero()).min(F::one());
+            Some(SegmentIntersection::Point(self.start + d1 * t))
+        } else {
+            None
+        }
+    }
+
+    /// Points where the segment crosses or touches the circle, ordered from
+    /// its start. A tangent gives one point.
+    pub fn intersect_circle(&self, circle: &Circle<S, F>) -> Vec<Point2D<F, S>> {
+        let d = self.end - self.start;
+        let f = self.start - circle.center;
+        let tolerance = relative_tolerance::<F>() * circle.radius.max(d.length());
+        let length_squared = d.square_length();
+        if length_squared <= tolerance * tolerance {
+            return if (f.length() - circle.radius).abs() <= tolerance {
+                vec![self.start]
+            } else {
+                Vec::new()
+            };
+        }
+
+        // Closest point to the center, then half the chord to either side.
+        let t = -f.dot(d) / length_squared;
+        let distance = (f + d * t).length();
+        let half_chord_squared = (circle.radius - distance) * (circle.radius + distance);
+        let ts = if circle.radius - distance < -tolerance {
+            vec![]
+        } else if circle.radius - distance <= tolerance {
+            vec![t]
+        } else {
+            let dt = (half_chord_squared / length_squared).sqrt();
+            vec![t - dt, t + dt]
+        };
+
+        let slack = tolerance / length_squared.sqrt();
+        ts.into_iter()
+            .filter(|&t| -slack <= t && t <= F::one() + slack)
+            .map(|t| self.start + d * t.max(F::zero()).min(F::one()))
+            .collect()
+    }
+
+    /// Points where the segment crosses or touches the edges of `polygon`,
+    /// ordered from its start. Edges it overlaps contribute their ends, and
+    /// points shared by neighboring edges are only returned once.
+    pub fn intersect_polygon(&self, polygon: &[Point2D<F, S>]) -> Vec<Point2D<F, S>> {
+        let d = self.end - self.start;
+        let mut points = Vec::new();
+        for edge in edges(polygon) {
+            match self.intersect_segment(&edge) {
+                Some(SegmentIntersection::Point(p)) => points.push(p),
+                Some(SegmentIntersection::Overlap(line)) => {
+                    points.push(line.start);
+                    points.push(line.end);
+                }
+                None => {}
+            }
+        }
+
+        points.sort_by(|a, b| compare((*a - self.start).dot(d), (*b - self.start).dot(d)));
+        let tolerance = relative_tolerance::<F>() * d.length().max(polygon_size(polygon));
+        points.dedup_by(|a, b| a.distance_to(*b) <= tolerance);
+        points
+    }
+
+    /// Distance from `point` to the closest point of the segment.
+    pub fn distance_to(&self, point: Point2D<F, S>) -> F {
+        let d = self.end - self.start;
+        let length_squared = d.square_length();
+        if length_squared == F::zero() {
+            return point.distance_to(self.start);
+        }
+        let t = (point - self.start).dot(d) / length_squared;
+        point.distance_to(self.start + d * t.max(F::zero()).min(F::one()))
+    }
+}
+
+impl<S, F: Scalar> Circle<S, F> {
+    /// Points where the circles cross or touch. Circles that touch give one
+    /// point, identical circles none, as they share every point.
+    pub fn intersect_circle(&self, other: &Circle<S, F>) -> Vec<Point2D<F, S>> {
+        let v = other.center - self.center;
+        let distance = v.length();
+        let tolerance = relative_tolerance::<F>() * self.radius.max(other.radius);
+        if distance > self.radius + other.radius + tolerance
+            || distance < (self.radius - other.radius).abs() - tolerance
+            || distance <= tolerance
+        {
+            return Vec::new();
+        }
+
+        // Distance from this center to the chord between the points, along
+        // the line between the centers.
+        let a = (self.radius * self.radius - other.radius * other.radius + distance * distance)
+            / (F::of(2.) * distance);
+        let direction = v / distance;
+        let middle = self.center + direction * a;
+        let height = (self.radius - a.abs()).max(F::zero()) * (self.radius + a.abs());
+        if self.radius - a.abs() <= tolerance {
+            return vec![middle];
+        }
+
+        let offset = Vector2D::new(-direction.y, direction.x) * height.sqrt();
+        vec![middle + offset, middle - offset]
+    }
+}
+
+/// Whether `point` lies inside the polygon given by its corners, by the
+/// even-odd rule. Points on the boundary count as inside.
+pub fn polygon_contains<S, F: Scalar>(polygon: &[Point2D<F, S>], point: Point2D<F, S>) -> bool {
+    let tolerance = relative_tolerance::<F>() * polygon_size(polygon);
+    let mut inside = false;
+    for edge in edges(polygon) {
+        if edge.distance_to(point) <= tolerance {
+            return true;
+        }
+
+        // Counts crossings of a ray to the right, edges are half open in y
+        // so a ray through a corner counts it once.
+        let (a, b) = (edge.start, edge.end);
+        if (a.y > point.y) != (b.y > point.y) {
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// All intersections between `lines`, as the indices of both segments
+/// (smaller first) and where they meet, ordered by the indices. Segments
+/// with coordinates that aren't finite intersect nothing.
+///
+/// A vertical line sweeps over the segments from left to right and only
+/// segments that it crosses at the same time and whose y ranges overlap are
+/// tested. That takes `O(n log n + k)` for `k` such pairs, which is far less
+/// than a test of every pair for segments spread over the plane, but still
+/// `O(n²)` when most of them share x and y ranges, e.g. long parallel ones.
+pub fn intersect_all<S, F: Scalar>(
+    lines: &[Line<S, F>],
+) -> Vec<(usize, usize, SegmentIntersection<S, F>)> {
+    // Widened by the tolerance of `intersect_segment`, which is relative to
+    // the longer segment, so segments touching within it aren't skipped.
+    let bounds: Vec<_> = lines
+        .iter()
+        .map(|line| {
+            let margin = (relative_tolerance::<F>() * (line.end - line.start).length())
+                .max(F::min_positive_value());
+            let margin = Vector2D::new(margin, margin);
+            (
+                line.start.min(line.end) - margin,
+                line.start.max(line.end) + margin,
+            )
+        })
+        .collect();
+
+    let mut order: Vec<_> = (0..lines.len())
+        .filter(|&i| {
+            let (min, max) = bounds[i];
+            min.x.is_finite() && min.y.is_finite() && max.x.is_finite() && max.y.is_finite()
+        })
+        .collect();
+    order.sort_by(|&a, &b| compare(bounds[a].0.x, bounds[b].0.x));
+
+    let mut intersections = Vec::new();
+    let mut active: Vec<usize> = Vec::new();
+    for i in order {
+        let (min, max) = bounds[i];
+        active.retain(|&j| bounds[j].1.x >= min.x);
+
+        for &j in &active {
+            let (other_min, other_max) = bounds[j];
+            if other_max.y < min.y || other_min.y > max.y {
+                continue;
+            }
+            let (first, second) = (i.min(j), i.max(j));
+            if let Some(intersection) = lines[first].intersect_segment(&lines[second]) {
+                intersections.push((first, second, intersection));
+            }
+        }
+        active.push(i);
+    }
+
+    intersections.sort_by_key(|&(i, j, _)| (i, j));
+    intersections
+}
+
+/// Relative tolerance of comparisons, a few units of rounding.
+fn relative_tolerance<F: Scalar>() -> F {
+    F::epsilon() * F::of(64.)
+}
+
+/// Orders NaN after everything else instead of panicking on it.
+fn compare<F: Scalar>(a: F, b: F) -> Ordering {
+    a.total_cmp(&b)
+}
+
+/// The edges of a closed polygon, the last corner connects to the first.
+fn edges<S, F: Scalar>(polygon: &[Point2D<F, S>]) -> impl Iterator<Item = Line<S, F>> + '_ {
+    let next = polygon.iter().cycle().skip(1);
+    polygon
+        .iter()
+        .zip(next)
+        .map(|(&start, &end)| Line { start, end })
+}
+
+/// Largest coordinate of the polygon, for scaling the tolerance.
+fn polygon_size<S, F: Scalar>(polygon: &[Point2D<F, S>]) -> F {
+    polygon
+        .iter()
+        .map(|p| p.x.abs().max(p.y.abs()))
+        .fold(F::zero(), F::max)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::geometry::test::generator;
+    use crate::geometry::CanvasSpace;
+    use euclid::point2;
+
+    type L = Line<CanvasSpace, f64>;
+
+    fn point(
+        intersection: Option<SegmentIntersection<CanvasSpace, f64>>,
+    ) -> Point2D<f64, CanvasSpace> {
+        match intersection {
+            Some(SegmentIntersection::Point(p)) => p,
+            other => panic!("expected a point, got {:?}", other),
+        }
+    }
+
+    fn overlap(intersection: Option<SegmentIntersection<CanvasSpace, f64>>) -> L {
+        match intersection {
+            Some(SegmentIntersection::Overlap(line)) => line,
+            other => panic!("expected an overlap, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_intersects_segments() {
+        let a = L::new(0., 0., 2., 2.);
+        assert_eq!(
+            point(a.intersect_segment(&L::new(0., 2., 2., 0.))),
+            point2(1., 1.)
+        );
+        // Touching at an end, and a T junction.
+        assert_eq!(
+            point(a.intersect_segment(&L::new(2., 2., 3., 0.))),
+            point2(2., 2.)
+        );
+        assert_eq!(
+            point(a.intersect_segment(&L::new(1., 1., 2., 0.))),
+            point2(1., 1.)
+        );
+        assert!(a.intersect_segment(&L::new(3., 0., 3., 5.)).is_none());
+        // Parallel but apart.
+        assert!(a.intersect_segment(&L::new(0., 1., 2., 3.)).is_none());
+        // Zero length segments are points.
+        assert_eq!(
+            point(a.intersect_segment(&L::new(1., 1., 1., 1.))),
+            point2(1., 1.)
+        );
+        assert!(a.intersect_segment(&L::new(1., 0., 1., 0.)).is_none());
+        let p = L::new(5., 5., 5., 5.);
+        assert_eq!(point(p.intersect_segment(&p.clone())), point2(5., 5.));
+    }
+
+    #[test]
+    fn it_intersects_collinear_segments() {
+        let a = L::new(0., 0., 4., 0.);
+        let o = overlap(a.intersect_segment(&L::new(6., 0., 2., 0.)));
+        assert_eq!((o.start, o.end), (point2(2., 0.), point2(4., 0.)));
+        let o = overlap(a.intersect_segment(&L::new(1., 0., 3., 0.)));
+        assert_eq!((o.start, o.end), (point2(1., 0.), point2(3., 0.)));
+        let o = overlap(L::new(1., 0., 3., 0.).intersect_segment(&a));
+        assert_eq!((o.start, o.end), (point2(1., 0.), point2(3., 0.)));
+        // Collinear segments sharing only an end, and apart.
+        assert_eq!(
+            point(a.intersect_segment(&L::new(4., 0., 7., 0.))),
+            point2(4., 0.)
+        );
+        assert!(a.intersect_segment(&L::new(5., 0., 7., 0.)).is_none());
+
+        // Rounding doesn't break the overlap of long diagonal segments.
+        let b = Line::<CanvasSpace, f32>::new(0., 0., 1e4 / 3., 1e4 / 7.);
+        let c = Line::<CanvasSpace, f32>::new(1e4 / 6., 1e4 / 14., 1e4, 3e4 / 7.);
+        assert!(matches!(
+            b.intersect_segment(&c),
+            Some(SegmentIntersection::Overlap(_))
+        ));
+    }
+
+    #[test]
+    fn it_intersects_circles() {
+        let circle = Circle::new(point2(1., 1.), 1.);
+        let a = L::new(-1., 1., 3., 1.);
+        assert_eq!(
+            a.intersect_circle(&circle),
+            vec![point2(0., 1.), point2(2., 1.)]
+        );
+        // Reversed, and starting inside.
+        assert_eq!(
+            L::new(3., 1., 1., 1.).intersect_circle(&circle),
+            vec![point2(2., 1.)]
+        );
+        // Tangent, missing and fully inside.
+        assert_eq!(
+            L::new(-1., 2., 3., 2.).intersect_circle(&circle),
+            vec![point2(1., 2.)]
+        );
+        assert!(L::new(-1., 3., 3., 3.).intersect_circle(&circle).is_empty());
+        assert!(L::new(0.5, 1., 1.5, 1.)
+            .intersect_circle(&circle)
+            .is_empty());
+        assert_eq!(
+            L::new(1., 0., 1., 0.).intersect_circle(&circle),
+            vec![point2(1., 0.)]
+        );
+
+        let other = Circle::new(point2(3., 1.), 1.);
+        assert_eq!(circle.intersect_circle(&other), vec![point2(2., 1.)]);
+        let other = Circle::new(point2(2., 1.), 1.);
+        let points = circle.intersect_circle(&other);
+        assert_eq!(points.len(), 2);
+        for p in points {
+            assert!((p.distance_to(circle.center) - 1.).abs() < 1e-12);
+            assert!((p.distance_to(other.center) - 1.).abs() < 1e-12);
+        }
+        // Touching from inside, nested, apart and identical.
+        let inner = Circle::new(point2(1.5, 1.), 0.5);
+        assert_eq!(circle.intersect_circle(&inner), vec![point2(2., 1.)]);
+        assert!(circle
+            .intersect_circle(&Circle::new(point2(1., 1.), 0.5))
+            .is_empty());
+        assert!(circle
+            .intersect_circle(&Circle::new(point2(5., 1.), 1.))
+            .is_empty());
+        assert!(circle.intersect_circle(&circle.clone()).is_empty());
+    }
+
+    #[test]
+    fn it_intersects_polygons() {
+        // An L shape with a concave corner at (1, 1).
+        let polygon = [
+            point2(0., 0.),
+            point2(2., 0.),
+            point2(2., 1.),
+            point2(1., 1.),
+            point2(1., 2.),
+            point2(0., 2.),
+        ];
+        assert!(polygon_contains(&polygon, point2(0.5, 1.5)));
+        assert!(polygon_contains(&polygon, point2(1.5, 0.5)));
+        assert!(!polygon_contains(&polygon, point2(1.5, 1.5)));
+        assert!(!polygon_contains(&polygon, point2(-1., 1.)));
+        // On an edge, on a corner and level with corners.
+        assert!(polygon_contains(&polygon, point2(2., 0.5)));
+        assert!(polygon_contains(&polygon, point2(1., 1.)));
+        assert!(polygon_contains(&polygon, point2(0.5, 1.)));
+        assert!(!polygon_contains(&polygon, point2(-1., 0.)));
+        assert!(!polygon_contains::<CanvasSpace, f64>(&[], point2(0., 0.)));
+
+        let points = L::new(-1., 0.5, 3., 0.5).intersect_polygon(&polygon);
+        assert_eq!(points, vec![point2(0., 0.5), point2(2., 0.5)]);
+        // Through the concave corner, which two edges share.
+        let points = L::new(0., 2., 2., 0.).intersect_polygon(&polygon);
+        assert_eq!(points, vec![point2(0., 2.), point2(1., 1.), point2(2., 0.)]);
+        // Along an edge.
+        let points = L::new(3., 1., -1., 1.).intersect_polygon(&polygon);
+        assert_eq!(points, vec![point2(2., 1.), point2(1., 1.), point2(0., 1.)]);
+    }
+
+    #[test]
+    fn it_finds_all_intersections() {
+        // Deterministic scatter of short segments, with some collinear ones.
+        let mut next = generator(4321);
+        let mut lines: Vec<L> = (0..300)
+            .map(|_| {
+                let (x, y) = (next() * 100., next() * 100.);
+                L::new(x, y, x + next() * 10. - 5., y + next() * 10. - 5.)
+            })
+            .collect();
+        lines.push(L::new(10., 10., 10., 20.));
+        lines.push(L::new(10., 15., 10., 30.));
+        lines.push(L::new(0., 50., 20., 50.));
+        lines.push(L::new(20., 50., 40., 50.));
+
+        let mut expected = Vec::new();
+        for i in 0..lines.len() {
+            for j in i + 1..lines.len() {
+                if lines[i].intersect_segment(&lines[j]).is_some() {
+                    expected.push((i, j));
+                }
+            }
+        }
+        let found: Vec<_> = intersect_all(&lines)
+            .into_iter()
+            .map(|(i, j, _)| (i, j))
+            .collect();
+        assert!(expected.len() > 10);
+        assert_eq!(found, expected);
+        assert!(intersect_all::<CanvasSpace, f64>(&[]).is_empty());
+    }
+
+    #[test]
+    fn it_finds_intersections_within_the_tolerance() {
+        // The ranges of the segments are apart, but less than the tolerance.
+        let lines = [
+            L::new(0., 0., 1., 0.),
+            L::new(1. + 1e-14, -1., 1. + 1e-14, 1.),
+            L::new(f64::NAN, 0., 1., 0.),
+        ];
+        assert!(lines[0].intersect_segment(&lines[1]).is_some());
+        let found: Vec<_> = intersect_all(&lines)
+            .into_iter()
+            .map(|(i, j, _)| (i, j))
+            .collect();
+        assert_eq!(found, vec![(0, 1)]);
+
+        let polygon = [point2(0., 0.), point2(f64::NAN, 1.), point2(1., 1.)];
+        let points = L::new(-1., 0.5, 2., 0.5).intersect_polygon(&polygon);
+        assert_eq!(points, vec![point2(0.5, 0.5)]);
+    }
+}
@@ -1,8 +1,16 @@
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::ops::{AddAssign, DivAssign, MulAssign, SubAssign};
+
 use euclid::Point2D;
 use euclid::Vector2D;
+use num_traits::float::TotalOrder;
+use num_traits::{Float, FloatConst};
 
 pub mod debug;
+pub mod intersection;
 pub mod nurbs;
+pub mod quadtree;
 
 #[derive(Clone, Copy, Debug)]
 pub struct ModelSpace;
@@ -16,63 +24,160 @@ pub struct ScreenSpace;
 #[derive(Clone, Copy, Debug)]
 pub struct PixelSpace;
 
+/// Floating point types the geometry is computed in, `f32` or `f64`. It is
+/// converted to `f32` only when it becomes [`graphics::primitives`](crate::graphics::primitives).
+pub trait Scalar:
+    Float
+    + FloatConst
+    + TotalOrder
+    + Default
+    + Debug
+    + Sum
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+    + 'static
+{
+    /// Converts a constant, rounding it for `f32`.
+    fn of(value: f64) -> Self;
+    fn as_f32(self) -> f32;
+    fn as_f64(self) -> f64;
+}
+
+impl Scalar for f32 {
+    fn of(value: f64) -> f32 {
+        value as f32
+    }
+
+    fn as_f32(self) -> f32 {
+        self
+    }
+
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Scalar for f64 {
+    fn of(value: f64) -> f64 {
+        value
+    }
+
+    fn as_f32(self) -> f32 {
+        self as f32
+    }
+
+    fn as_f64(self) -> f64 {
+        self
+    }
+}
+
 #[derive(Clone, Debug)]
-pub struct Line<S> {
-    pub start: Point2D<f32, S>,
-    pub end: Point2D<f32, S>,
+pub struct Line<S, F = f32> {
+    pub start: Point2D<F, S>,
+    pub end: Point2D<F, S>,
 }
 
-impl<S> Line<S> {
-    pub fn new(start_x: f32, start_y: f32, end_x: f32, end_y: f32) -> Line<S> {
+impl<S, F: Scalar> Line<S, F> {
+    pub fn new(start_x: F, start_y: F, end_x: F, end_y: F) -> Line<S, F> {
         Line {
             start: Point2D::new(start_x, start_y),
             end: Point2D::new(end_x, end_y),
         }
     }
 
-    pub fn from_tuple((start, end): (Point2D<f32, S>, Point2D<f32, S>)) -> Option<Line<S>> {
+    pub fn from_tuple((start, end): (Point2D<F, S>, Point2D<F, S>)) -> Option<Line<S, F>> {
         if start.x.is_nan() || start.y.is_nan() || end.x.is_nan() || end.y.is_nan() {
             None
         } else {
             Some(Line { start, end })
         }
     }
+
+    pub fn midpoint(&self) -> Point2D<F, S> {
+        self.start.lerp(self.end, F::of(0.5))
+    }
+
+    /// Point where both segments cross, `None` if they don't or are parallel.
+    pub fn intersect(&self, other: &Line<S, F>) -> Option<Point2D<F, S>> {
+        let d1 = self.end - self.start;
+        let d2 = other.end - other.start;
+        let denom = d1.cross(d2);
+        if denom.abs() < F::of(0.000001) {
+            return None;
+        }
+
+        let v = other.start - self.start;
+        let t1 = v.cross(d2) / denom;
+        let t2 = v.cross(d1) / denom;
+        let unit = F::zero()..=F::one();
+        if unit.contains(&t1) && unit.contains(&t2) {
+            Some(self.start + d1 * t1)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
-pub struct Ray<S> {
-    pub origin: Point2D<f32, S>,
-    pub direction: Vector2D<f32, S>,
+pub struct Circle<S, F = f32> {
+    pub center: Point2D<F, S>,
+    pub radius: F,
 }
 
-impl<S> Ray<S> {
-    pub fn intersect_line(&self, line: &Line<S>) -> Option<f32> {
+impl<S, F: Scalar> Circle<S, F> {
+    pub fn new(center: Point2D<F, S>, radius: F) -> Circle<S, F> {
+        Circle { center, radius }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Ray<S, F = f32> {
+    pub origin: Point2D<F, S>,
+    pub direction: Vector2D<F, S>,
+}
+
+impl<S, F: Scalar> Ray<S, F> {
+    pub fn intersect_line(&self, line: &Line<S, F>) -> Option<F> {
         let v1 = self.origin - line.start;
         let s = line.end - line.start;
         let v3 = Vector2D::new(-self.direction.y, self.direction.x);
 
         let dot = s.dot(v3);
-        if dot.abs() < 0.000001 {
+        if dot.abs() < F::of(0.000001) {
             return None;
         }
 
         let t1 = s.cross(v1) / dot;
         let t2 = v1.dot(v3) / dot;
 
-        if t1 >= 0.0 && (t2 >= 0.0 && t2 <= 1.0) {
+        if t1 >= F::zero() && (F::zero()..=F::one()).contains(&t2) {
             return Some(t1);
         }
 
         None
     }
 
-    pub fn intersect_lines(&self, lines: impl Iterator<Item = Line<S>>) -> Option<f32> {
+    pub fn intersect_lines(&self, lines: impl Iterator<Item = Line<S, F>>) -> Option<F> {
         lines
             .filter_map(|l| self.intersect_line(&l))
             .min_by(|a, b| a.partial_cmp(b).unwrap())
     }
 
-    pub fn at(&self, t: f32) -> Point2D<f32, S> {
+    pub fn at(&self, t: F) -> Point2D<F, S> {
         self.origin + self.direction * t
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+
+    /// Deterministic numbers between 0 and 1.
+    pub(crate) fn generator(mut seed: u32) -> impl FnMut() -> f64 {
+        move || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 8) as f64 / (1 << 24) as f64
+        }
+    }
+}
@@ -2,22 +2,129 @@
 
 use euclid::Vector3D;
 
+use super::Scalar;
+
+mod editing;
+mod fitting;
+mod measuring;
+mod object;
+mod surface;
+
+pub use fitting::Parameterization;
+pub use object::{SplineObject, SurfaceObject};
+pub use surface::{Surface, SurfaceMesh};
+
+/// Limits the subdivisions of [`Spline::tessellate`], e.g. at cusps.
+const MAX_TESSELLATION_DEPTH: usize = 12;
+
+/// Why the parts passed to [`Spline::new`] don't make up a curve.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SplineError {
+    /// The degree must be at least 1 and less than the number of control
+    /// points.
+    InvalidDegree { degree: usize, points: usize },
+    /// There must be as many knots as control points plus the degree plus 1.
+    InvalidKnotCount { expected: usize, found: usize },
+    /// The knot at `index` is smaller than the one before it.
+    DecreasingKnots { index: usize },
+    /// The interior knot at `index` repeats more often than the degree, which
+    /// would break the curve apart.
+    KnotMultiplicity { index: usize },
+    /// The domain of the curve must be from 0 to 1.
+    KnotsNotNormalized { start: f64, end: f64 },
+    /// Weights must be positive and finite.
+    InvalidWeight { index: usize },
+    /// Fitting needs at least as many samples as control points.
+    NotEnoughSamples { needed: usize, found: usize },
+    /// The samples can't be fitted, e.g. because they are all the same.
+    DegenerateSamples,
+    /// Row `row` of a surface's control grid is not as long as the first.
+    RaggedControlGrid { row: usize },
+}
+
+/// `count` knots from 0 to 1 for a curve that starts in the first control
+/// point and ends in the last one.
+pub fn clamped_uniform_knots<F: Scalar>(degree: usize, count: usize) -> Vec<F> {
+    let spans = count.saturating_sub(degree).max(1);
+    (0..count + degree + 1)
+        .map(|i| F::of((i.saturating_sub(degree) as f64 / spans as f64).min(1.)))
+        .collect()
+}
+
+/// Uniform knots for `count` control points that are not clamped, so the
+/// curve starts and ends away from the first and last control point. The
+/// knots outside of the domain from 0 to 1 continue the uniform spacing.
+pub fn open_knots<F: Scalar>(degree: usize, count: usize) -> Vec<F> {
+    let spans = count.saturating_sub(degree).max(1);
+    (0..count + degree + 1)
+        .map(|i| F::of((i as f64 - degree as f64) / spans as f64))
+        .collect()
+}
+
+/// Knots for a closed curve through `count` control points, which are
+/// followed by the first `degree` of them again. See [`Spline::periodic`].
+pub fn periodic_knots<F: Scalar>(degree: usize, count: usize) -> Vec<F> {
+    open_knots(degree, count + degree)
+}
+
 /// Represents a nurbs curve.
 ///
-/// Note that the knots vector is normalized (range 0 to 1).
-#[derive(Debug)]
-pub struct Spline<T> {
+/// Note that the knots vector is normalized (range 0 to 1). The coordinates,
+/// weights and knots are `F`, `f32` or `f64`.
+#[derive(Clone, Debug)]
+pub struct Spline<T, F = f32> {
     degree: usize,
-    c_points: Vec<(f32, Vector3D<f32, T>)>,
-    knots: Vec<f32>,
+    c_points: Vec<(F, Vector3D<F, T>)>,
+    knots: Vec<F>,
 }
 
-impl<T> Spline<T> {
+impl<T, F: Scalar> Spline<T, F> {
+    /// Creates a curve from weighted control points, given as `(weight,
+    /// point)`, and normalized knots.
     pub fn new(
         degree: usize,
-        c_points: Vec<(f32, Vector3D<f32, T>)>,
-        knots: Vec<f32>,
-    ) -> Spline<T> {
+        c_points: Vec<(F, Vector3D<F, T>)>,
+        knots: Vec<F>,
+    ) -> Result<Spline<T, F>, SplineError> {
+        let spline = Spline::from_parts(degree, c_points, knots);
+        spline.validate()?;
+        Ok(spline)
+    }
+
+    /// A clamped curve with uniform knots through the first and last of
+    /// `c_points`.
+    pub fn clamped_uniform(
+        degree: usize,
+        c_points: Vec<(F, Vector3D<F, T>)>,
+    ) -> Result<Spline<T, F>, SplineError> {
+        let knots = clamped_uniform_knots(degree, c_points.len());
+        Spline::new(degree, c_points, knots)
+    }
+
+    /// A closed curve around `c_points`, the first `degree` of them are
+    /// appended to close it.
+    pub fn periodic(
+        degree: usize,
+        mut c_points: Vec<(F, Vector3D<F, T>)>,
+    ) -> Result<Spline<T, F>, SplineError> {
+        let count = c_points.len();
+        if degree == 0 || degree >= count {
+            return Err(SplineError::InvalidDegree {
+                degree,
+                points: count,
+            });
+        }
+        c_points.extend_from_within(..degree);
+        Spline::new(degree, c_points, periodic_knots(degree, count))
+    }
+
+    /// Skips the validation, for parts that are known to be valid or knots
+    /// that are normalized afterwards.
+    fn from_parts(
+        degree: usize,
+        c_points: Vec<(F, Vector3D<F, T>)>,
+        knots: Vec<F>,
+    ) -> Spline<T, F> {
         Spline {
             degree,
             c_points,
@@ -25,9 +132,70 @@ impl<T> Spline<T> {
         }
     }
 
-    fn get_point_0(&self, bases: &mut [f32], k: usize) -> Vector3D<f32, T> {
-        let mut bases_sum = 0.;
-        let mut point = Vector3D::new(0., 0., 0.);
+    fn validate(&self) -> Result<(), SplineError> {
+        let (degree, points) = (self.degree, self.c_points.len());
+        if degree == 0 || degree >= points {
+            return Err(SplineError::InvalidDegree { degree, points });
+        }
+
+        let expected = points + degree + 1;
+        if self.knots.len() != expected {
+            return Err(SplineError::InvalidKnotCount {
+                expected,
+                found: self.knots.len(),
+            });
+        }
+        if let Some(index) =
+            (1..expected).find(|&i| self.knots[i].is_nan() || self.knots[i - 1] > self.knots[i])
+        {
+            return Err(SplineError::DecreasingKnots { index });
+        }
+        if let Some(index) = (degree + 1..points).find(|&i| {
+            self.knots[i - degree..=i]
+                .iter()
+                .all(|&knot| knot == self.knots[i])
+        }) {
+            return Err(SplineError::KnotMultiplicity { index });
+        }
+
+        let (start, end) = self.domain();
+        if start.abs() > F::epsilon() || (end - F::one()).abs() > F::epsilon() {
+            return Err(SplineError::KnotsNotNormalized {
+                start: start.as_f64(),
+                end: end.as_f64(),
+            });
+        }
+
+        if let Some(index) = self
+            .c_points
+            .iter()
+            .position(|&(weight, _)| !(weight > F::zero() && weight.is_finite()))
+        {
+            return Err(SplineError::InvalidWeight { index });
+        }
+        Ok(())
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    /// The control points with their weights, as `(weight, point)`.
+    pub fn control_points(&self) -> &[(F, Vector3D<F, T>)] {
+        &self.c_points
+    }
+
+    pub fn knots(&self) -> &[F] {
+        &self.knots
+    }
+
+    fn multiplicity(&self, u: F) -> usize {
+        self.knots.iter().filter(|&&knot| knot == u).count()
+    }
+
+    fn get_point_0(&self, bases: &mut [F], k: usize) -> Vector3D<F, T> {
+        let mut bases_sum = F::zero();
+        let mut point = Vector3D::zero();
         for (i, base) in bases.iter_mut().enumerate().take(self.degree + 1) {
             let c_point = self.c_points[k - self.degree + i];
             *base *= c_point.0;
@@ -37,65 +205,285 @@ impl<T> Spline<T> {
         point / bases_sum
     }
 
-    fn _basis_its_0(&self, k: usize, u: f32) -> Vec<f32> {
-        let mut bases = vec![0.; self.degree + 1];
-        let mut ls = vec![0.; self.degree + 1];
-        let mut rs = vec![0.; self.degree + 1];
+    fn _basis_its_0(&self, k: usize, u: F) -> Vec<F> {
+        let mut bases = vec![F::zero(); self.degree + 1];
+        let mut ls = vec![F::zero(); self.degree + 1];
+        let mut rs = vec![F::zero(); self.degree + 1];
         self.basis_its_0_no_alloc(k, u, &mut bases[..], &mut ls[..], &mut rs[..]);
         bases
     }
 
-    fn basis_its_0_no_alloc(
-        &self,
-        k: usize,
-        u: f32,
-        bases: &mut [f32],
-        ls: &mut [f32],
-        rs: &mut [f32],
-    ) {
-        bases[0] = 1.;
-        for j in 1..=self.degree {
-            let mut saved = 0.;
+    fn basis_its_0_no_alloc(&self, k: usize, u: F, bases: &mut [F], ls: &mut [F], rs: &mut [F]) {
+        basis_its_0(&self.knots, self.degree, k, u, bases, ls, rs);
+    }
+
+    fn find_span(&self, u: F) -> usize {
+        find_span(&self.knots, self.degree, self.c_points.len(), u)
+    }
+
+    /// Parameter range of the curve.
+    pub fn domain(&self) -> (F, F) {
+        (self.knots[self.degree], self.knots[self.c_points.len()])
+    }
+
+    /// The nonzero basis functions in span `k` at `u` and their derivatives
+    /// up to order `n`, `ders[i][j]` is the `i`-th derivative of the `j`-th
+    /// function (The NURBS Book, A2.3).
+    fn basis_derivatives(&self, k: usize, u: F, n: usize) -> Vec<Vec<F>> {
+        let p = self.degree;
+        let mut ndu = vec![vec![F::zero(); p + 1]; p + 1];
+        let mut ls = vec![F::zero(); p + 1];
+        let mut rs = vec![F::zero(); p + 1];
+        ndu[0][0] = F::one();
+        for j in 1..=p {
             ls[j] = u - self.knots[k + 1 - j];
             rs[j] = self.knots[k + j] - u;
-            let mut r = 0;
-            while r < j {
-                let tmp = bases[r] / (rs[r + 1] + ls[j - r]);
-                bases[r] = saved + rs[r + 1] * tmp;
+            let mut saved = F::zero();
+            for r in 0..j {
+                // Lower triangle holds the knot differences.
+                ndu[j][r] = rs[r + 1] + ls[j - r];
+                let tmp = ndu[r][j - 1] / ndu[j][r];
+                ndu[r][j] = saved + rs[r + 1] * tmp;
                 saved = ls[j - r] * tmp;
-                r += 1;
             }
-            bases[j] = saved;
+            ndu[j][j] = saved;
+        }
+
+        let mut ders = vec![vec![F::zero(); p + 1]; n + 1];
+        for j in 0..=p {
+            ders[0][j] = ndu[j][p];
+        }
+
+        let n = n.min(p);
+        let mut a = [vec![F::zero(); p + 1], vec![F::zero(); p + 1]];
+        for r in 0..=p {
+            let (mut s1, mut s2) = (0, 1);
+            a[0][0] = F::one();
+            for d in 1..=n {
+                let mut der = F::zero();
+                let rd = r as isize - d as isize;
+                let pd = p - d;
+                if rd >= 0 {
+                    a[s2][0] = a[s1][0] / ndu[pd + 1][rd as usize];
+                    der = a[s2][0] * ndu[rd as usize][pd];
+                }
+                let j1 = if rd >= -1 { 1 } else { (-rd) as usize };
+                let j2 = if r <= pd + 1 { d - 1 } else { p - r };
+                for j in j1..=j2 {
+                    let i = (rd + j as isize) as usize;
+                    a[s2][j] = (a[s1][j] - a[s1][j - 1]) / ndu[pd + 1][i];
+                    der += a[s2][j] * ndu[i][pd];
+                }
+                if r <= pd {
+                    a[s2][d] = -a[s1][d - 1] / ndu[pd + 1][r];
+                    der += a[s2][d] * ndu[r][pd];
+                }
+                ders[d][r] = der;
+                std::mem::swap(&mut s1, &mut s2);
+            }
+        }
+
+        let mut factor = F::of(p as f64);
+        for (d, row) in ders.iter_mut().enumerate().take(n + 1).skip(1) {
+            for der in row.iter_mut() {
+                *der *= factor;
+            }
+            factor *= F::of((p - d) as f64);
+        }
+        ders
+    }
+
+    /// The point at `u` followed by its first `n` derivatives, `u` is clamped
+    /// to the domain (The NURBS Book, A4.2).
+    pub fn derivatives(&self, u: F, n: usize) -> Vec<Vector3D<F, T>> {
+        let (start, end) = self.domain();
+        let u = u.clamp(start, end);
+        let k = self.find_span(u);
+        let ders = self.basis_derivatives(k, u, n);
+
+        // Derivatives of the weighted points and of the weight function.
+        let mut points = vec![Vector3D::zero(); n + 1];
+        let mut weights = vec![F::zero(); n + 1];
+        for d in 0..=n.min(self.degree) {
+            for (j, &base) in ders[d].iter().enumerate() {
+                let (weight, point) = self.c_points[k - self.degree + j];
+                points[d] += point * (base * weight);
+                weights[d] += base * weight;
+            }
+        }
+
+        let mut result: Vec<Vector3D<F, T>> = Vec::with_capacity(n + 1);
+        for d in 0..=n {
+            let mut v = points[d];
+            for i in 1..=d {
+                v -= result[d - i] * (binomial::<F>(d, i) * weights[i]);
+            }
+            result.push(v / weights[0]);
+        }
+        result
+    }
+
+    pub fn point_at(&self, u: F) -> Vector3D<F, T> {
+        self.derivatives(u, 0)[0]
+    }
+
+    /// Unit tangent at `u`, pointing in the direction of increasing `u`.
+    ///
+    /// Where the first derivative vanishes, e.g. at a cusp or at repeated
+    /// control points, it is the direction the curve leaves `u` in, given by
+    /// the first derivative that doesn't vanish. It is zero only if the curve
+    /// doesn't move at all.
+    pub fn tangent_at(&self, u: F) -> Vector3D<F, T> {
+        let ders = self.derivatives(u, self.degree.max(1));
+        match self.first_moving(u, &ders) {
+            Some(k) => {
+                // At the end of the domain the curve arrives instead, an even
+                // derivative points back along it there.
+                let tangent = ders[k].normalize();
+                if u >= self.domain().1 && k % 2 == 0 {
+                    -tangent
+                } else {
+                    tangent
+                }
+            }
+            None => Vector3D::zero(),
+        }
+    }
+
+    /// Unit normal at `u`, the tangent turned left in the xy plane.
+    pub fn normal_at(&self, u: F) -> Vector3D<F, T> {
+        let tangent = self.tangent_at(u);
+        Vector3D::new(-tangent.y, tangent.x, F::zero())
+    }
+
+    /// Signed curvature at `u` of the curve in the xy plane, positive where
+    /// it turns left. Its inverse is the radius of the osculating circle.
+    ///
+    /// `None` where the first derivative vanishes, the curvature isn't
+    /// defined there.
+    pub fn curvature_at(&self, u: F) -> Option<F> {
+        let ders = self.derivatives(u, 2);
+        if self.first_moving(u, &ders) != Some(1) {
+            return None;
+        }
+        let (d1, d2) = (ders[1], ders[2]);
+        Some((d1.x * d2.y - d1.y * d2.x) / d1.x.hypot(d1.y).powi(3))
+    }
+
+    /// Order of the first derivative in `ders`, the point at `u` followed by
+    /// its derivatives, that doesn't vanish, `None` if all do.
+    ///
+    /// Each derivative is compared with the rounding error of its sum over the
+    /// control points, so only derivatives that are zero but for rounding
+    /// vanish, however large the higher ones are.
+    fn first_moving(&self, u: F, ders: &[Vector3D<F, T>]) -> Option<usize> {
+        let (start, end) = self.domain();
+        let u = u.clamp(start, end);
+        let k = self.find_span(u);
+        let n = (ders.len() - 1).min(self.degree);
+        let basis = self.basis_derivatives(k, u, n);
+        let c_points = &self.c_points[k - self.degree..=k];
+
+        let weight: F = basis[0]
+            .iter()
+            .zip(c_points)
+            .map(|(&b, &(w, _))| b * w)
+            .sum();
+        let magnitude = ders[0].length();
+        (1..=n).find(|&d| {
+            let error: F = basis[d]
+                .iter()
+                .zip(c_points)
+                .map(|(&b, &(w, point))| (b * w).abs() * (point.length() + magnitude))
+                .sum();
+            ders[d].length() > F::of(64.) * F::epsilon() * error / weight
+        })
+    }
+
+    /// Points along the curve, placed so that no chord deviates more than
+    /// `tolerance` from the curve. Flat spans get few points and tight bends
+    /// many. For a tolerance in pixels, divide it by
+    /// [`DrawingContext::pixels_per_unit`](crate::canvas::DrawingContext::pixels_per_unit).
+    pub fn tessellate(&self, tolerance: F) -> Vec<Vector3D<F, T>> {
+        let mut knots = self.knots[self.degree..=self.c_points.len()].to_vec();
+        knots.dedup();
+
+        // Every span is split into one piece per degree first, so a chord
+        // can't miss a bend of the span with its midpoint on the chord.
+        let pieces = self.degree.max(1);
+        let mut points = vec![self.point_at(knots[0])];
+        for span in knots.windows(2) {
+            let mut a = span[0];
+            let mut pa = *points.last().unwrap();
+            for i in 1..=pieces {
+                let b = span[0] + (span[1] - span[0]) * F::of(i as f64 / pieces as f64);
+                let pb = self.point_at(b);
+                self.subdivide((a, pa), (b, pb), tolerance, 0, &mut points);
+                a = b;
+                pa = pb;
+            }
+        }
+        points
+    }
+
+    /// Adds the points after `a` up to and including `b`.
+    fn subdivide(
+        &self,
+        (a, pa): (F, Vector3D<F, T>),
+        (b, pb): (F, Vector3D<F, T>),
+        tolerance: F,
+        depth: usize,
+        points: &mut Vec<Vector3D<F, T>>,
+    ) {
+        let m = (a + b) / F::of(2.);
+        let pm = self.point_at(m);
+        if depth < MAX_TESSELLATION_DEPTH && chord_distance(pm, pa, pb) > tolerance {
+            self.subdivide((a, pa), (m, pm), tolerance, depth + 1, points);
+            self.subdivide((m, pm), (b, pb), tolerance, depth + 1, points);
+        } else {
+            points.push(pb);
         }
     }
 
-    pub fn get_points(&self, step_count: usize) -> Vec<Vector3D<f32, T>> {
+    /// `step_count` points evenly spaced in the parameter, including both
+    /// ends of the curve.
+    pub fn get_points(&self, step_count: usize) -> Vec<Vector3D<F, T>> {
+        let (start, end) = self.domain();
+        if step_count < 2 {
+            return (0..step_count).map(|_| self.point_at(start)).collect();
+        }
+
         // Preallocate buffers. Create a bigger buffer and then split it into
         // three parts to avoid multiple allocations.
-        let mut float_buff = vec![0.; (self.degree + 1) * 3];
+        let mut float_buff = vec![F::zero(); (self.degree + 1) * 3];
         let (bases, rest) = float_buff.split_at_mut(self.degree + 1);
         let (ls, rs) = rest.split_at_mut(self.degree + 1);
         let mut points = Vec::with_capacity(step_count);
 
-        let step = 1. / (step_count - 1) as f32;
-
-        points.push(self.c_points[0].1);
+        // Clamped curves start and end exactly in their control points.
+        points.push(if self.multiplicity(start) > self.degree {
+            self.c_points[0].1
+        } else {
+            self.point_at(start)
+        });
 
-        let mut u = self.knots[self.degree] + step;
         let mut k = self.degree;
-        while k < self.c_points.len() {
-            while (self.knots[k] - self.knots[k + 1]).abs() < f32::EPSILON && self.knots[k] < 1. {
+        for i in 1..step_count - 1 {
+            // Computed from `i` rather than summed up, so rounding can't add
+            // another point before the end.
+            let u = start + (end - start) * F::of(i as f64 / (step_count - 1) as f64);
+            while k < self.c_points.len() - 1 && u >= self.knots[k + 1] {
                 k += 1;
             }
-            while u < self.knots[k + 1] {
-                self.basis_its_0_no_alloc(k, u, &mut bases[..], &mut ls[..], &mut rs[..]);
-                points.push(self.get_point_0(&mut bases[..], k));
-                u += step;
-            }
-            k += 1;
+            self.basis_its_0_no_alloc(k, u, &mut bases[..], &mut ls[..], &mut rs[..]);
+            points.push(self.get_point_0(&mut bases[..], k));
         }
 
-        points.push(self.c_points[self.c_points.len() - 1].1);
+        points.push(if self.multiplicity(end) > self.degree {
+            self.c_points[self.c_points.len() - 1].1
+        } else {
+            self.point_at(end)
+        });
 
         debug_assert_eq!(points.len(), step_count);
 
@@ -103,16 +491,64 @@ impl<T> Spline<T> {
     }
 }
 
+/// The `degree + 1` nonzero basis functions of `knots` in span `k` at `u`,
+/// written to `bases`. `ls` and `rs` are scratch space of the same length.
+fn basis_its_0<F: Scalar>(
+    knots: &[F],
+    degree: usize,
+    k: usize,
+    u: F,
+    bases: &mut [F],
+    ls: &mut [F],
+    rs: &mut [F],
+) {
+    bases[0] = F::one();
+    for j in 1..=degree {
+        let mut saved = F::zero();
+        ls[j] = u - knots[k + 1 - j];
+        rs[j] = knots[k + j] - u;
+        let mut r = 0;
+        while r < j {
+            let tmp = bases[r] / (rs[r + 1] + ls[j - r]);
+            bases[r] = saved + rs[r + 1] * tmp;
+            saved = ls[j - r] * tmp;
+            r += 1;
+        }
+        bases[j] = saved;
+    }
+}
+
+/// Index `k` of the knot span with `knots[k] <= u < knots[k + 1]` for `count`
+/// control points, the end of the domain belongs to the last span.
+fn find_span<F: Scalar>(knots: &[F], degree: usize, count: usize, u: F) -> usize {
+    let k = knots.partition_point(|&knot| knot <= u);
+    k.saturating_sub(1).clamp(degree, count - 1)
+}
+
+/// Distance of `point` from the chord through `a` and `b`.
+fn chord_distance<T, F: Scalar>(point: Vector3D<F, T>, a: Vector3D<F, T>, b: Vector3D<F, T>) -> F {
+    let chord = b - a;
+    let length = chord.length();
+    if length < F::epsilon() {
+        (point - a).length()
+    } else {
+        (point - a).cross(chord).length() / length
+    }
+}
+
+fn binomial<F: Scalar>(n: usize, k: usize) -> F {
+    F::of((0..k).fold(1., |acc, i| acc * (n - i) as f64 / (i + 1) as f64))
+}
+
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
 
     use super::*;
     use euclid::vec3;
 
-    #[test]
-    fn it_creates_a_circle() {
+    pub(crate) fn circle() -> Spline<()> {
         let pi = std::f32::consts::PI;
-        let a = Spline::<()> {
+        Spline::<()> {
             degree: 2,
             c_points: vec![
                 (1., vec3(1., 0., 0.)),
@@ -139,8 +575,12 @@ mod test {
                 2. * pi / 2. / pi,
                 2. * pi / 2. / pi,
             ],
-        };
+        }
+    }
 
+    #[test]
+    fn it_creates_a_circle() {
+        let a = circle();
         assert_eq!(
             a.get_points(5),
             vec![
@@ -152,4 +592,218 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn it_evaluates_derivatives_on_a_circle() {
+        let a = circle();
+        for i in 0..=40 {
+            let u = i as f32 / 40.;
+            let point = a.point_at(u);
+            assert!((point.length() - 1.).abs() < 1e-5);
+
+            // Counter-clockwise, so the tangent is the radius turned left and
+            // the normal points to the center.
+            let tangent = a.tangent_at(u);
+            assert!((tangent - vec3(-point.y, point.x, 0.)).length() < 1e-4);
+            assert!((a.normal_at(u) + point).length() < 1e-4);
+            assert!((a.curvature_at(u).unwrap() - 1.).abs() < 1e-3);
+
+            // The raw derivative matches a finite difference.
+            let h = 1e-3;
+            let (start, end) = ((u - h).max(0.), (u + h).min(1.));
+            let diff = (a.point_at(end) - a.point_at(start)) / (end - start);
+            assert!((a.derivatives(u, 1)[1] - diff).length() < 0.05 * diff.length());
+        }
+        assert!((a.point_at(0.25) - vec3(0., 1., 0.)).length() < 1e-6);
+    }
+
+    #[test]
+    fn it_tessellates_adaptively() {
+        let a = circle();
+        let coarse = a.tessellate(1e-2);
+        let fine = a.tessellate(1e-4);
+        assert!(coarse.len() < fine.len());
+        for points in &[coarse, fine] {
+            assert!((points[0] - vec3(1., 0., 0.)).length() < 1e-6);
+            assert!((points[points.len() - 1] - vec3(1., 0., 0.)).length() < 1e-5);
+        }
+        // The sagitta of the chords stays within the tolerance.
+        for pair in a.tessellate(1e-3).windows(2) {
+            let middle = (pair[0] + pair[1]) / 2.;
+            assert!(1. - middle.length() < 1e-3 + 1e-5);
+        }
+
+        // Straight spans need no extra points.
+        let line = Spline::<()> {
+            degree: 2,
+            c_points: vec![
+                (1., vec3(0., 0., 0.)),
+                (1., vec3(1., 1., 0.)),
+                (1., vec3(2., 2., 0.)),
+            ],
+            knots: vec![0., 0., 0., 1., 1., 1.],
+        };
+        assert_eq!(line.tessellate(1e-3).len(), 3);
+    }
+
+    #[test]
+    fn it_validates_the_parts() {
+        let points = || {
+            (0..4)
+                .map(|i| (1., vec3(i as f32, (i % 2) as f32, 0.)))
+                .collect::<Vec<_>>()
+        };
+        assert!(Spline::<()>::clamped_uniform(3, points()).is_ok());
+        assert_eq!(
+            Spline::<()>::clamped_uniform(4, points()).unwrap_err(),
+            SplineError::InvalidDegree {
+                degree: 4,
+                points: 4
+            }
+        );
+        assert_eq!(
+            Spline::<()>::new(2, points(), vec![0., 0., 0., 1., 1., 1.]).unwrap_err(),
+            SplineError::InvalidKnotCount {
+                expected: 7,
+                found: 6
+            }
+        );
+        assert_eq!(
+            Spline::<()>::new(2, points(), vec![0., 0., 0., 0.6, 0.4, 1., 1.]).unwrap_err(),
+            SplineError::DecreasingKnots { index: 4 }
+        );
+        assert_eq!(
+            Spline::<()>::new(1, points(), vec![0., 0., 0.5, 0.5, 1., 1.]).unwrap_err(),
+            SplineError::KnotMultiplicity { index: 3 }
+        );
+        assert_eq!(
+            Spline::<()>::new(2, points(), vec![0., 0., 0., 1., 2., 2., 2.]).unwrap_err(),
+            SplineError::KnotsNotNormalized { start: 0., end: 2. }
+        );
+        let mut weighted = points();
+        weighted[2].0 = 0.;
+        assert_eq!(
+            Spline::<()>::clamped_uniform(2, weighted).unwrap_err(),
+            SplineError::InvalidWeight { index: 2 }
+        );
+
+        let a = Spline::<()>::clamped_uniform(2, points()).unwrap();
+        assert!(a.get_points(0).is_empty());
+        assert_eq!(a.get_points(1), vec![vec3(0., 0., 0.)]);
+    }
+
+    #[test]
+    fn it_builds_knots() {
+        assert_eq!(
+            clamped_uniform_knots::<f32>(2, 5),
+            [0., 0., 0., 1. / 3., 2. / 3., 1., 1., 1.]
+        );
+        assert_eq!(open_knots::<f32>(1, 3), [-0.5, 0., 0.5, 1., 1.5]);
+
+        // A periodic curve closes smoothly.
+        let square = vec![
+            (1., vec3(1., 1., 0.)),
+            (1., vec3(-1., 1., 0.)),
+            (1., vec3(-1., -1., 0.)),
+            (1., vec3(1., -1., 0.)),
+        ];
+        let a = Spline::<()>::periodic(3, square).unwrap();
+        assert!((a.point_at(0.) - a.point_at(1.)).length() < 1e-5);
+        assert!((a.tangent_at(0.) - a.tangent_at(1.)).length() < 1e-4);
+        let points = a.get_points(9);
+        assert!((points[0] - points[8]).length() < 1e-5);
+    }
+
+    #[test]
+    fn it_finds_tangents_at_cusps() {
+        // The first derivative vanishes halfway, where the curve turns back.
+        let a = Spline::<()>::clamped_uniform(
+            3,
+            vec![
+                (1., vec3(0., 0., 0.)),
+                (1., vec3(1., 1., 0.)),
+                (1., vec3(0., 1., 0.)),
+                (1., vec3(1., 0., 0.)),
+            ],
+        )
+        .unwrap();
+        assert!(a.derivatives(0.5, 1)[1].length() < 1e-6);
+        assert!((a.tangent_at(0.5) - vec3(0., -1., 0.)).length() < 1e-5);
+        assert!((a.normal_at(0.5) - vec3(1., 0., 0.)).length() < 1e-5);
+        assert_eq!(a.curvature_at(0.5), None);
+        assert!(a.curvature_at(0.25).unwrap().is_finite());
+
+        // Repeated control points at both ends of a straight line.
+        let a = Spline::<()>::clamped_uniform(
+            2,
+            vec![
+                (1., vec3(0., 0., 0.)),
+                (1., vec3(0., 0., 0.)),
+                (1., vec3(2., 0., 0.)),
+                (1., vec3(2., 0., 0.)),
+            ],
+        )
+        .unwrap();
+        for &u in &[0., 0.5, 1.] {
+            assert!((a.tangent_at(u) - vec3(1., 0., 0.)).length() < 1e-5);
+        }
+        assert_eq!(a.curvature_at(0.), None);
+
+        // A curve that doesn't move has no direction.
+        let a = Spline::<()>::clamped_uniform(1, vec![(1., vec3(1., 1., 0.)); 2]).unwrap();
+        assert_eq!(a.tangent_at(0.5), vec3(0., 0., 0.));
+    }
+
+    #[test]
+    fn it_finds_tangents_on_many_spans() {
+        // Higher derivatives grow with the number of spans, the first one
+        // still gives the direction.
+        let c_points = (0..50)
+            .map(|i| {
+                let zigzag = if i % 2 == 0 { 0.01 } else { -0.01 };
+                (1., vec3(i as f32, zigzag, 0.))
+            })
+            .collect();
+        let a = Spline::<()>::clamped_uniform(3, c_points).unwrap();
+        for i in 0..=1000 {
+            let u = i as f32 / 1000.;
+            let d1 = a.derivatives(u, 1)[1];
+            assert!((a.tangent_at(u) - d1.normalize()).length() < 1e-5, "{}", u);
+            assert!(a.normal_at(u).y > 0.99);
+            assert!(a.curvature_at(u).is_some());
+        }
+    }
+
+    #[test]
+    fn it_evaluates_in_f64() {
+        // A millimetre wide circle ten kilometres away, in metres, which f32
+        // can't resolve.
+        let center = vec3(1e4, -1e4, 0.);
+        let w = 0.5_f64.sqrt();
+        let corners = [
+            (1., 0.),
+            (1., 1.),
+            (0., 1.),
+            (-1., 1.),
+            (-1., 0.),
+            (-1., -1.),
+            (0., -1.),
+            (1., -1.),
+            (1., 0.),
+        ];
+        let c_points = corners
+            .iter()
+            .enumerate()
+            .map(|(i, &(x, y))| {
+                let weight = if i % 2 == 0 { 1. } else { w };
+                (weight, vec3(x, y, 0.) * 1e-3 + center)
+            })
+            .collect();
+        let knots = vec![0., 0., 0., 0.25, 0.25, 0.5, 0.5, 0.75, 0.75, 1., 1., 1.];
+        let a = Spline::<(), f64>::new(2, c_points, knots).unwrap();
+        for point in a.get_points(20).into_iter().chain(a.tessellate(1e-7)) {
+            assert!(((point - center).length() - 1e-3).abs() < 1e-10);
+        }
+        assert!((a.length() - 2e-3 * std::f64::consts::PI).abs() < 1e-9);
+    }
 }
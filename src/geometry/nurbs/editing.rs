@@ -0,0 +1,509 @@
+//! Shape preserving edits of splines, the algorithms are from The NURBS Book
+//! (Piegl, L. & Tiller, W., 1997) and work on the weighted control points.
+
+use euclid::Vector3D;
+
+use super::{binomial, Spline};
+use crate::geometry::Scalar;
+
+/// Control point multiplied by its weight, followed by the weight.
+type Weighted<T, F> = (Vector3D<F, T>, F);
+
+fn mix<T, F: Scalar>(a: Weighted<T, F>, b: Weighted<T, F>, t: F) -> Weighted<T, F> {
+    let s = F::one() - t;
+    (a.0 * s + b.0 * t, a.1 * s + b.1 * t)
+}
+
+fn distance<T, F: Scalar>(a: Weighted<T, F>, b: Weighted<T, F>) -> F {
+    ((a.0 - b.0).square_length() + (a.1 - b.1).powi(2)).sqrt()
+}
+
+impl<T, F: Scalar> Spline<T, F> {
+    fn weighted(&self) -> Vec<Weighted<T, F>> {
+        self.c_points.iter().map(|&(w, p)| (p * w, w)).collect()
+    }
+
+    fn from_weighted(degree: usize, points: Vec<Weighted<T, F>>, knots: Vec<F>) -> Spline<T, F> {
+        Spline {
+            degree,
+            c_points: points.into_iter().map(|(p, w)| (w, p / w)).collect(),
+            knots,
+        }
+    }
+
+    /// Scales the knots to the range 0 to 1.
+    fn normalize_knots(&mut self) {
+        let (start, end) = self.domain();
+        for knot in &mut self.knots {
+            *knot = (*knot - start) / (end - start);
+        }
+    }
+
+    /// Inserts the knot `u` up to `times` times, as long as its multiplicity
+    /// stays at most the degree. Returns how often it was inserted (Boehm's
+    /// algorithm, A5.1).
+    pub fn insert_knot(&mut self, u: F, times: usize) -> usize {
+        let (start, end) = self.domain();
+        if u < start || u > end {
+            return 0;
+        }
+        let p = self.degree;
+        let s = self.multiplicity(u);
+        let r = times.min(p.saturating_sub(s));
+        if r == 0 {
+            return 0;
+        }
+
+        // Unlike `find_span` this takes the last span containing `u`, also
+        // at the end of the domain.
+        let k = self.knots.iter().rposition(|&knot| knot <= u).unwrap();
+        let n = self.c_points.len() - 1;
+        let pw = self.weighted();
+
+        let mut knots = Vec::with_capacity(self.knots.len() + r);
+        knots.extend_from_slice(&self.knots[..=k]);
+        knots.resize(knots.len() + r, u);
+        knots.extend_from_slice(&self.knots[k + 1..]);
+
+        let mut qw = vec![(Vector3D::zero(), F::zero()); n + 1 + r];
+        qw[..=k - p].copy_from_slice(&pw[..=k - p]);
+        qw[k - s + r..].copy_from_slice(&pw[k - s..]);
+
+        let mut rw = pw[k - p..=k - s].to_vec();
+        let mut l = k - p;
+        for j in 1..=r {
+            l = k - p + j;
+            for i in 0..=p - j - s {
+                let alpha = (u - self.knots[l + i]) / (self.knots[i + k + 1] - self.knots[l + i]);
+                rw[i] = mix(rw[i], rw[i + 1], alpha);
+            }
+            qw[l] = rw[0];
+            qw[k + r - j - s] = rw[p - j - s];
+        }
+        if k - s > l {
+            qw[l + 1..k - s].copy_from_slice(&rw[1..k - s - l]);
+        }
+
+        *self = Spline::from_weighted(p, qw, knots);
+        r
+    }
+
+    /// Removes the knot `u` up to `times` times, as long as the curve moves
+    /// less than `tolerance`. Returns how often it was removed (A5.8).
+    pub fn remove_knot(&mut self, u: F, times: usize, tolerance: F) -> usize {
+        let (start, end) = self.domain();
+        let s = self.multiplicity(u);
+        if u <= start || u >= end || s == 0 {
+            return 0;
+        }
+
+        // The tolerance applies to the weighted points, scale it so that the
+        // curve itself stays within it.
+        let w_min = self
+            .c_points
+            .iter()
+            .map(|c| c.0)
+            .fold(F::infinity(), F::min);
+        let p_max = self
+            .c_points
+            .iter()
+            .map(|c| c.1.length())
+            .fold(F::zero(), F::max);
+        let tolerance = tolerance * w_min / (F::one() + p_max);
+
+        let p = self.degree as isize;
+        let ord = p + 1;
+        let n = self.c_points.len() as isize - 1;
+        let r = self.knots.iter().rposition(|&knot| knot == u).unwrap() as isize;
+        let s = s as isize;
+        let knots = &self.knots;
+        let knot = |i: isize| knots[i as usize];
+        let mut pw = self.weighted();
+
+        let mut first = r - p;
+        let mut last = r - s;
+        let mut temp = vec![(Vector3D::zero(), F::zero()); (2 * p + 1) as usize];
+        let mut t = 0;
+        while t < times.min(s as usize) as isize {
+            let off = first - 1;
+            temp[0] = pw[off as usize];
+            temp[(last + 1 - off) as usize] = pw[(last + 1) as usize];
+            let (mut i, mut j) = (first, last);
+            let (mut ii, mut jj) = (1, last - off);
+            while j - i > t {
+                let alf_i = (u - knot(i)) / (knot(i + ord + t) - knot(i));
+                let alf_j = (u - knot(j - t)) / (knot(j + ord) - knot(j - t));
+                let (a, b) = (pw[i as usize], temp[(ii - 1) as usize]);
+                temp[ii as usize] = (
+                    (a.0 - b.0 * (F::one() - alf_i)) / alf_i,
+                    (a.1 - b.1 * (F::one() - alf_i)) / alf_i,
+                );
+                let (a, b) = (pw[j as usize], temp[(jj + 1) as usize]);
+                temp[jj as usize] = (
+                    (a.0 - b.0 * alf_j) / (F::one() - alf_j),
+                    (a.1 - b.1 * alf_j) / (F::one() - alf_j),
+                );
+                i += 1;
+                ii += 1;
+                j -= 1;
+                jj -= 1;
+            }
+
+            let removable = if j - i < t {
+                distance(temp[(ii - 1) as usize], temp[(jj + 1) as usize]) <= tolerance
+            } else {
+                let alf_i = (u - knot(i)) / (knot(i + ord + t) - knot(i));
+                let expected = mix(temp[(ii - 1) as usize], temp[(ii + t + 1) as usize], alf_i);
+                distance(pw[i as usize], expected) <= tolerance
+            };
+            if !removable {
+                break;
+            }
+
+            let (mut i, mut j) = (first, last);
+            while j - i > t {
+                pw[i as usize] = temp[(i - off) as usize];
+                pw[j as usize] = temp[(j - off) as usize];
+                i += 1;
+                j -= 1;
+            }
+            first -= 1;
+            last += 1;
+            t += 1;
+        }
+
+        if t == 0 {
+            return 0;
+        }
+
+        let mut knots = self.knots.clone();
+        knots.drain((r + 1 - t) as usize..=r as usize);
+
+        // Points in the middle of the changed range are left over.
+        let f_out = (2 * r - s - p) / 2;
+        let (mut i, mut j) = (f_out, f_out);
+        for k in 1..t {
+            if k % 2 == 1 {
+                i += 1;
+            } else {
+                j -= 1;
+            }
+        }
+        for k in i + 1..=n {
+            pw[j as usize] = pw[k as usize];
+            j += 1;
+        }
+        pw.truncate(j as usize);
+
+        *self = Spline::from_weighted(self.degree, pw, knots);
+        t as usize
+    }
+
+    /// The same curve with knots of full multiplicity at both ends of the
+    /// domain, so it starts and ends in a control point.
+    fn clamped(&self) -> Spline<T, F>
+    where
+        T: Clone,
+    {
+        let mut spline = self.clone();
+        let p = spline.degree;
+        let (start, end) = spline.domain();
+        spline.insert_knot(start, p);
+        spline.insert_knot(end, p);
+
+        let before = spline.knots.iter().filter(|&&knot| knot < start).count();
+        let after = spline.knots.iter().filter(|&&knot| knot > end).count();
+        if before > 0 {
+            spline.knots.drain(..before - 1);
+            spline.knots[0] = start;
+            spline.c_points.drain(..before - 1);
+        }
+        if after > 0 {
+            let len = spline.knots.len();
+            spline.knots.truncate(len - after + 1);
+            *spline.knots.last_mut().unwrap() = end;
+            let len = spline.c_points.len();
+            spline.c_points.truncate(len - after + 1);
+        }
+        spline
+    }
+
+    /// Splits the curve at `u` into two curves, each with normalized knots.
+    /// `None` if `u` is not inside of the domain.
+    pub fn split(&self, u: F) -> Option<(Spline<T, F>, Spline<T, F>)>
+    where
+        T: Clone,
+    {
+        let (start, end) = self.domain();
+        if u <= start || u >= end {
+            return None;
+        }
+
+        let mut spline = self.clamped();
+        let p = spline.degree;
+        spline.insert_knot(u, p);
+        let index = spline.knots.iter().position(|&knot| knot == u).unwrap();
+
+        let mut knots = spline.knots[..index].to_vec();
+        knots.resize(knots.len() + p + 1, u);
+        let mut left = Spline::from_parts(p, spline.c_points[..index].to_vec(), knots);
+
+        let mut knots = vec![u; p + 1];
+        knots.extend_from_slice(&spline.knots[index + p..]);
+        let mut right = Spline::from_parts(p, spline.c_points[index - 1..].to_vec(), knots);
+
+        left.normalize_knots();
+        right.normalize_knots();
+        Some((left, right))
+    }
+
+    /// The spans of the curve as Bézier curves, each with knots from 0 to 1.
+    pub fn bezier_segments(&self) -> Vec<Spline<T, F>>
+    where
+        T: Clone,
+    {
+        let p = self.degree;
+        let mut spline = self.clamped();
+        let (start, end) = spline.domain();
+        let mut interior = spline.knots.clone();
+        interior.retain(|&knot| start < knot && knot < end);
+        interior.dedup();
+        for &u in &interior {
+            spline.insert_knot(u, p);
+        }
+
+        let bezier_knots: Vec<F> = (0..2 * (p + 1))
+            .map(|i| F::of((i / (p + 1)) as f64))
+            .collect();
+        (0..=interior.len())
+            .map(|i| {
+                let c_points = spline.c_points[i * p..=(i + 1) * p].to_vec();
+                Spline::from_parts(p, c_points, bezier_knots.clone())
+            })
+            .collect()
+    }
+
+    /// Raises the degree by `times` without changing the curve, by raising
+    /// the degree of each Bézier segment and removing the knots added between
+    /// them again.
+    pub fn elevate_degree(&mut self, times: usize)
+    where
+        T: Clone,
+    {
+        if times == 0 {
+            return;
+        }
+
+        let p = self.degree;
+        let q = p + times;
+        let clamped = self.clamped();
+        let (start, end) = clamped.domain();
+        let mut interior: Vec<(F, usize)> = Vec::new();
+        for &knot in clamped.knots.iter().filter(|&&k| start < k && k < end) {
+            match interior.last_mut() {
+                Some((u, m)) if *u == knot => *m += 1,
+                _ => interior.push((knot, 1)),
+            }
+        }
+
+        let mut points: Vec<Weighted<T, F>> = Vec::new();
+        for (i, segment) in clamped.bezier_segments().iter().enumerate() {
+            let pw = segment.weighted();
+            let skip = if i == 0 { 0 } else { 1 };
+            for k in skip..=q {
+                let range = k.saturating_sub(times)..=k.min(p);
+                points.push(range.fold((Vector3D::zero(), F::zero()), |(point, w), j| {
+                    let c = binomial::<F>(p, j) * binomial::<F>(times, k - j) / binomial::<F>(q, k);
+                    (point + pw[j].0 * c, w + pw[j].1 * c)
+                }));
+            }
+        }
+
+        let mut knots = vec![start; q + 1];
+        for &(u, _) in &interior {
+            knots.resize(knots.len() + q, u);
+        }
+        knots.resize(knots.len() + q + 1, end);
+
+        let mut spline = Spline::from_weighted(q, points, knots);
+        let size = self
+            .c_points
+            .iter()
+            .map(|c| c.1.length())
+            .fold(F::one(), F::max);
+        for &(u, m) in &interior {
+            spline.remove_knot(u, p - m, size * F::of(1e-4));
+        }
+        *self = spline;
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::geometry::test::generator;
+    use euclid::vec3;
+
+    fn curve() -> Spline<()> {
+        Spline::new(
+            3,
+            vec![
+                (1., vec3(0., 0., 0.)),
+                (2., vec3(1., 2., 0.)),
+                (0.5, vec3(3., 3., 0.)),
+                (1., vec3(4., 0., 0.)),
+                (1.5, vec3(6., -1., 0.)),
+                (1., vec3(7., 2., 0.)),
+            ],
+            vec![0., 0., 0., 0., 0.3, 0.55, 1., 1., 1., 1.],
+        )
+        .unwrap()
+    }
+
+    /// Compares `a` between `start` and `end` to all of `b`.
+    fn assert_same_shape<F: Scalar>(a: &Spline<(), F>, b: &Spline<(), F>, (start, end): (F, F)) {
+        let tolerance = F::of(1e-4).min(F::epsilon().sqrt() * F::of(0.5));
+        for i in 0..=100 {
+            let v = F::of(i as f64 / 100.);
+            let u = start + (end - start) * v;
+            let (pa, pb) = (a.point_at(u), b.point_at(v));
+            assert!(
+                (pa - pb).length() < tolerance,
+                "{:?} != {:?} at {:?}",
+                pa,
+                pb,
+                u
+            );
+        }
+    }
+
+    /// Rational curves of degree 1 to 4 in a 10 by 10 square, with random
+    /// interior knots (some repeated) or, for every fourth, periodic knots.
+    fn random_curves(count: usize) -> Vec<Spline<(), f64>> {
+        let mut next = generator(987);
+        (0..count)
+            .map(|i| {
+                let p = 1 + (next() * 4.) as usize;
+                let n = p + 1 + (next() * 6.) as usize;
+                let c_points = (0..n)
+                    .map(|_| (0.5 + next() * 1.5, vec3(next() * 10., next() * 10., 0.)))
+                    .collect();
+                if i % 4 == 3 {
+                    return Spline::periodic(p, c_points).unwrap();
+                }
+
+                let mut interior: Vec<f64> = Vec::new();
+                while interior.len() < n - p - 1 {
+                    // Interior knots repeat at most `p` times.
+                    let last = interior.last().copied();
+                    let run = interior.iter().filter(|&&k| Some(k) == last).count();
+                    let repeat = last.filter(|_| run < p && next() < 0.2);
+                    interior.push(repeat.unwrap_or_else(|| 0.05 + next() * 0.9));
+                }
+                interior.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let mut knots = vec![0.; p + 1];
+                knots.extend(interior);
+                knots.resize(n + p + 1, 1.);
+                Spline::new(p, c_points, knots).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_inserts_and_removes_knots() {
+        let a = curve();
+        let mut b = curve();
+        assert_eq!(b.insert_knot(0.3, 5), 2);
+        assert_eq!(b.insert_knot(0.7, 1), 1);
+        assert_eq!(b.c_points.len(), 9);
+        assert_same_shape(&a, &b, (0., 1.));
+
+        assert_eq!(b.remove_knot(0.7, 1, 1e-4), 1);
+        assert_eq!(b.remove_knot(0.3, 3, 1e-4), 2);
+        assert_eq!(b.knots, a.knots);
+        assert_same_shape(&a, &b, (0., 1.));
+
+        // Removing an original knot would change the curve.
+        assert_eq!(b.remove_knot(0.55, 1, 1e-4), 0);
+    }
+
+    #[test]
+    fn it_elevates_the_degree() {
+        let a = curve();
+        let mut b = curve();
+        b.elevate_degree(2);
+        assert_eq!(b.degree, 5);
+        assert_eq!(b.knots.len(), a.knots.len() + 2 * 4);
+        assert_same_shape(&a, &b, (0., 1.));
+    }
+
+    #[test]
+    fn it_splits_into_parts_and_bezier_segments() {
+        let a = curve();
+        let (left, right) = a.split(0.4).unwrap();
+        assert_same_shape(&a, &left, (0., 0.4));
+        assert_same_shape(&a, &right, (0.4, 1.));
+        assert!(a.split(1.).is_none());
+
+        let segments = a.bezier_segments();
+        assert_eq!(segments.len(), 3);
+        for (segment, (start, end)) in segments.iter().zip(&[(0., 0.3), (0.3, 0.55), (0.55, 1.)]) {
+            assert_eq!(segment.c_points.len(), 4);
+            assert!((segment.point_at(0.) - a.point_at(*start)).length() < 1e-4);
+            assert!((segment.point_at(0.5) - a.point_at((start + end) / 2.)).length() < 1e-4);
+            assert!((segment.point_at(1.) - a.point_at(*end)).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn it_splits_unclamped_curves() {
+        let a = Spline::<()>::periodic(2, curve().c_points).unwrap();
+        let (left, right) = a.split(0.5).unwrap();
+        assert_same_shape(&a, &left, (0., 0.5));
+        assert_same_shape(&a, &right, (0.5, 1.));
+
+        let mut b = a.clone();
+        b.elevate_degree(1);
+        assert_same_shape(&a, &b, (0., 1.));
+    }
+
+    #[test]
+    fn it_keeps_the_shape_of_random_curves() {
+        let mut next = generator(4242);
+        for a in random_curves(60) {
+            let p = a.degree;
+            let u = next();
+            let times = 1 + (next() * p as f64) as usize;
+
+            let mut b = a.clone();
+            let inserted = b.insert_knot(u, times);
+            assert!(inserted >= 1);
+            assert_same_shape(&a, &b, (0., 1.));
+            assert_eq!(b.remove_knot(u, inserted, 1e-9), inserted);
+            assert_eq!(b.c_points.len(), a.c_points.len());
+            assert_same_shape(&a, &b, (0., 1.));
+
+            let mut b = a.clone();
+            let raise = 1 + (next() * 2.) as usize;
+            b.elevate_degree(raise);
+            assert_eq!(b.degree, p + raise);
+            assert_same_shape(&a, &b, (0., 1.));
+
+            let u = 0.01 + next() * 0.98;
+            let (left, right) = a.split(u).unwrap();
+            assert_same_shape(&a, &left, (0., u));
+            assert_same_shape(&a, &right, (u, 1.));
+
+            let mut knots = a.knots.clone();
+            knots.retain(|&k| (0. ..=1.).contains(&k));
+            knots.dedup();
+            let segments = a.bezier_segments();
+            assert_eq!(segments.len(), knots.len() - 1);
+            for (segment, span) in segments.iter().zip(knots.windows(2)) {
+                assert_eq!(segment.c_points.len(), p + 1);
+                assert_same_shape(&a, segment, (span[0], span[1]));
+            }
+        }
+    }
+}
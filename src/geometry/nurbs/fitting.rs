@@ -0,0 +1,356 @@
+//! Curves through or near sampled points, following chapter 9 of The NURBS
+//! Book (Piegl, L. & Tiller, W., 1997).
+
+use euclid::Vector3D;
+
+use super::{Spline, SplineError};
+use crate::geometry::Scalar;
+
+/// How the parameters of the samples are chosen, which decides how the
+/// curve bends between them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parameterization {
+    /// Evenly spaced, only good for evenly spaced samples.
+    Uniform,
+    /// Spaced by the distance between the samples.
+    ChordLength,
+    /// Spaced by the square root of the distance, which overshoots less at
+    /// sharp turns.
+    Centripetal,
+}
+
+impl Parameterization {
+    /// Parameters from 0 to 1 for each of `points`.
+    pub fn parameters<T, F: Scalar>(self, points: &[Vector3D<F, T>]) -> Vec<F> {
+        let last = points.len().saturating_sub(1).max(1) as f64;
+        let uniform = || (0..points.len()).map(|i| F::of(i as f64 / last)).collect();
+        let exponent = match self {
+            Parameterization::Uniform => return uniform(),
+            Parameterization::ChordLength => F::one(),
+            Parameterization::Centripetal => F::of(0.5),
+        };
+
+        let steps: Vec<F> = points
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).length().powf(exponent))
+            .collect();
+        let total: F = steps.iter().copied().sum();
+        if total <= F::zero() {
+            return uniform();
+        }
+
+        let mut sum = F::zero();
+        let mut parameters = vec![F::zero()];
+        for step in steps {
+            sum += step;
+            parameters.push(sum / total);
+        }
+        *parameters.last_mut().unwrap() = F::one();
+        parameters
+    }
+}
+
+impl<T, F: Scalar> Spline<T, F> {
+    /// Nonrational curve passing through all `points`, with knots averaged
+    /// from their parameters.
+    pub fn interpolate(
+        degree: usize,
+        points: &[Vector3D<F, T>],
+        parameterization: Parameterization,
+    ) -> Result<Spline<T, F>, SplineError> {
+        check_degree(degree, points.len())?;
+        check_finite(points)?;
+        let parameters = parameterization.parameters(points);
+        let n = points.len() - 1;
+
+        let mut knots = vec![F::zero(); degree + 1];
+        for j in 1..=n - degree {
+            let sum: F = parameters[j..j + degree].iter().copied().sum();
+            knots.push(sum / F::of(degree as f64));
+        }
+        knots.resize(knots.len() + degree + 1, F::one());
+
+        let mut spline = Spline::unweighted(degree, points.len(), knots);
+        let mut matrix = vec![vec![0.; n + 1]; n + 1];
+        for (row, &u) in matrix.iter_mut().zip(&parameters) {
+            let (first, bases) = spline.basis_row(u);
+            for (j, base) in bases.into_iter().enumerate() {
+                row[first + j] = base.as_f64();
+            }
+        }
+        let rhs = points.iter().map(|&p| to_f64(p)).collect();
+
+        let solution = solve(matrix, rhs).ok_or(SplineError::DegenerateSamples)?;
+        spline.set_points(solution);
+        spline.validate()?;
+        Ok(spline)
+    }
+
+    /// Nonrational curve with `count` control points following `points` as
+    /// closely as possible in the least squares sense, it starts and ends in
+    /// the first and last point.
+    pub fn approximate(
+        degree: usize,
+        points: &[Vector3D<F, T>],
+        count: usize,
+        parameterization: Parameterization,
+    ) -> Result<Spline<T, F>, SplineError> {
+        check_degree(degree, count)?;
+        if count > points.len() {
+            return Err(SplineError::NotEnoughSamples {
+                needed: count,
+                found: points.len(),
+            });
+        }
+        if count == points.len() {
+            return Spline::interpolate(degree, points, parameterization);
+        }
+        check_finite(points)?;
+
+        let parameters = parameterization.parameters(points);
+        let (m, n) = (points.len() - 1, count - 1);
+
+        // Every span gets about the same number of samples.
+        let d = (m + 1) as f64 / (n - degree + 1) as f64;
+        let mut knots = vec![F::zero(); degree + 1];
+        for j in 1..=n - degree {
+            let i = (j as f64 * d) as usize;
+            let alpha = F::of(j as f64 * d - i as f64);
+            knots.push((F::one() - alpha) * parameters[i - 1] + alpha * parameters[i]);
+        }
+        knots.resize(knots.len() + degree + 1, F::one());
+
+        let mut spline = Spline::unweighted(degree, count, knots);
+
+        // Rows of the basis functions of the inner control points at the
+        // inner samples, the ends are fixed.
+        let (start, end) = (to_f64(points[0]), to_f64(points[m]));
+        let mut basis = vec![vec![0.; n - 1]; m - 1];
+        let mut residuals = Vec::with_capacity(m - 1);
+        for (k, row) in basis.iter_mut().enumerate() {
+            let (first, bases) = spline.basis_row(parameters[k + 1]);
+            let mut residual = to_f64(points[k + 1]);
+            for (j, base) in bases.into_iter().enumerate() {
+                let (i, base) = (first + j, base.as_f64());
+                if i == 0 {
+                    residual = sub(residual, scale(start, base));
+                } else if i == n {
+                    residual = sub(residual, scale(end, base));
+                } else {
+                    row[i - 1] = base;
+                }
+            }
+            residuals.push(residual);
+        }
+
+        // Normal equations.
+        let mut matrix = vec![vec![0.; n - 1]; n - 1];
+        let mut rhs = vec![[0.; 3]; n - 1];
+        for (row, residual) in basis.iter().zip(&residuals) {
+            for i in 0..n - 1 {
+                if row[i] == 0. {
+                    continue;
+                }
+                rhs[i] = add(rhs[i], scale(*residual, row[i]));
+                for j in 0..n - 1 {
+                    matrix[i][j] += row[i] * row[j];
+                }
+            }
+        }
+
+        let inner = solve(matrix, rhs).ok_or(SplineError::DegenerateSamples)?;
+        let mut solution = vec![start];
+        solution.extend(inner);
+        solution.push(end);
+        spline.set_points(solution);
+        spline.validate()?;
+        Ok(spline)
+    }
+
+    fn unweighted(degree: usize, count: usize, knots: Vec<F>) -> Spline<T, F> {
+        Spline::from_parts(degree, vec![(F::one(), Vector3D::zero()); count], knots)
+    }
+
+    fn set_points(&mut self, points: Vec<[f64; 3]>) {
+        for (c_point, [x, y, z]) in self.c_points.iter_mut().zip(points) {
+            c_point.1 = Vector3D::new(F::of(x), F::of(y), F::of(z));
+        }
+    }
+
+    /// Index of the first nonzero basis function at `u` and the values of
+    /// the nonzero ones.
+    fn basis_row(&self, u: F) -> (usize, Vec<F>) {
+        let k = self.find_span(u);
+        let mut ders = self.basis_derivatives(k, u, 0);
+        (k - self.degree, ders.swap_remove(0))
+    }
+}
+
+fn check_degree(degree: usize, points: usize) -> Result<(), SplineError> {
+    if degree == 0 || degree >= points {
+        Err(SplineError::InvalidDegree { degree, points })
+    } else {
+        Ok(())
+    }
+}
+
+fn check_finite<T, F: Scalar>(points: &[Vector3D<F, T>]) -> Result<(), SplineError> {
+    let finite = |p: &Vector3D<F, T>| p.x.is_finite() && p.y.is_finite() && p.z.is_finite();
+    if points.iter().all(finite) {
+        Ok(())
+    } else {
+        Err(SplineError::DegenerateSamples)
+    }
+}
+
+fn to_f64<T, F: Scalar>(p: Vector3D<F, T>) -> [f64; 3] {
+    [p.x.as_f64(), p.y.as_f64(), p.z.as_f64()]
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale(a: [f64; 3], s: f64) -> [f64; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+/// Solves `matrix * x = rhs` by Gaussian elimination with partial pivoting,
+/// `None` if the matrix is singular or not finite.
+fn solve(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<[f64; 3]>) -> Option<Vec<[f64; 3]>> {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        let value = matrix[pivot][col].abs();
+        if !value.is_finite() || value < 1e-12 {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+
+        let (upper, lower) = matrix.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (row, values) in (col + 1..).zip(lower) {
+            let factor = values[col] / pivot_row[col];
+            if factor == 0. {
+                continue;
+            }
+            for (value, pivot) in values[col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            rhs[row] = sub(rhs[row], scale(rhs[col], factor));
+        }
+    }
+
+    let mut x = vec![[0.; 3]; n];
+    for row in (0..n).rev() {
+        let mut sum = rhs[row];
+        for (x, value) in x[row + 1..].iter().zip(&matrix[row][row + 1..]) {
+            sum = sub(sum, scale(*x, *value));
+        }
+        x[row] = scale(sum, 1. / matrix[row][row]);
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use euclid::vec3;
+
+    fn circle_samples(count: usize) -> Vec<Vector3D<f32, ()>> {
+        (0..count)
+            .map(|i| {
+                let angle = i as f32 / (count - 1) as f32 * std::f32::consts::PI * 1.5;
+                vec3(angle.cos(), angle.sin(), 0.)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_interpolates_the_samples() {
+        let points = circle_samples(12);
+        for &parameterization in &[
+            Parameterization::Uniform,
+            Parameterization::ChordLength,
+            Parameterization::Centripetal,
+        ] {
+            let a = Spline::interpolate(3, &points, parameterization).unwrap();
+            assert!(a.validate().is_ok());
+            for (point, u) in points.iter().zip(parameterization.parameters(&points)) {
+                assert!((a.point_at(u) - *point).length() < 1e-4);
+            }
+            for point in a.get_points(50) {
+                assert!((point.length() - 1.).abs() < 1e-3);
+            }
+        }
+
+        assert_eq!(
+            Spline::interpolate(3, &points[..3], Parameterization::Uniform).unwrap_err(),
+            SplineError::InvalidDegree {
+                degree: 3,
+                points: 3
+            }
+        );
+        assert_eq!(
+            Spline::<()>::interpolate(
+                2,
+                &[
+                    vec3(0., 0., 0.),
+                    vec3(0., 0., 0.),
+                    vec3(1., 0., 0.),
+                    vec3(2., 1., 0.)
+                ],
+                Parameterization::ChordLength
+            )
+            .unwrap_err(),
+            SplineError::DegenerateSamples
+        );
+
+        let mut broken = points.clone();
+        broken[4].x = f32::NAN;
+        for count in &[6, 12] {
+            assert_eq!(
+                Spline::approximate(3, &broken, *count, Parameterization::ChordLength).unwrap_err(),
+                SplineError::DegenerateSamples
+            );
+        }
+    }
+
+    #[test]
+    fn it_approximates_the_samples() {
+        let points = circle_samples(200);
+        let a = Spline::approximate(3, &points, 10, Parameterization::ChordLength).unwrap();
+        assert_eq!(a.control_points().len(), 10);
+        assert!(a.validate().is_ok());
+        assert_eq!(a.point_at(0.), points[0]);
+        assert!((a.point_at(1.) - points[199]).length() < 1e-6);
+        for point in a.get_points(100) {
+            assert!((point.length() - 1.).abs() < 1e-3);
+        }
+
+        // Samples piling up in one place would give knots of too high
+        // multiplicity.
+        let mut piled = vec![vec3(0., 0., 0.)];
+        piled.resize(7, vec3(1., 1., 0.));
+        piled.push(vec3(2., 0., 0.));
+        assert_eq!(
+            Spline::<()>::approximate(1, &piled, 6, Parameterization::ChordLength).unwrap_err(),
+            SplineError::DegenerateSamples
+        );
+
+        assert_eq!(
+            Spline::approximate(3, &points[..5], 8, Parameterization::Uniform).unwrap_err(),
+            SplineError::NotEnoughSamples {
+                needed: 8,
+                found: 5
+            }
+        );
+    }
+}
@@ -0,0 +1,338 @@
+//! Lengths along splines and projections onto them.
+
+use euclid::Vector3D;
+
+use super::Spline;
+use crate::geometry::Scalar;
+
+/// Abscissas and weights of the 5 point Gauss-Legendre quadrature.
+const GAUSS: [(f64, f64); 5] = [
+    (0., 0.568_888_888_888_888_9),
+    (-0.538_469_310_105_683, 0.478_628_670_499_366_5),
+    (0.538_469_310_105_683, 0.478_628_670_499_366_5),
+    (-0.906_179_845_938_664, 0.236_926_885_056_189_1),
+    (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+];
+
+/// Knot spans are integrated and searched in this many pieces.
+const PIECES_PER_SPAN: usize = 4;
+const MAX_ITERATIONS: usize = 20;
+
+impl<T, F: Scalar> Spline<T, F> {
+    fn speed(&self, u: F) -> F {
+        self.derivatives(u, 1)[1].length()
+    }
+
+    fn integrate(&self, a: F, b: F) -> F {
+        let two = F::of(2.);
+        let (center, half) = ((a + b) / two, (b - a) / two);
+        GAUSS
+            .iter()
+            .map(|&(x, weight)| F::of(weight) * self.speed(center + half * F::of(x)))
+            .sum::<F>()
+            * half
+    }
+
+    /// Parameters splitting the domain into pieces which don't cross knots,
+    /// so the curve is smooth on each of them.
+    fn pieces(&self, per_span: usize) -> Vec<F> {
+        let mut knots = self.knots[self.degree..=self.c_points.len()].to_vec();
+        knots.dedup();
+        let mut pieces = vec![knots[0]];
+        for span in knots.windows(2) {
+            for i in 1..=per_span {
+                pieces.push(span[0] + (span[1] - span[0]) * F::of(i as f64 / per_span as f64));
+            }
+        }
+        pieces
+    }
+
+    /// Whether the curve ends where it starts, up to a gap relative to the
+    /// size of the control polygon and to the rounding of its coordinates.
+    pub fn is_closed(&self) -> bool {
+        let (start, end) = self.domain();
+        let (_, point) = self.c_points[0];
+        let (mut min, mut max) = (point, point);
+        let mut magnitude = F::zero();
+        for &(_, point) in &self.c_points {
+            min = min.min(point);
+            max = max.max(point);
+            magnitude = magnitude.max(point.x.abs().max(point.y.abs()).max(point.z.abs()));
+        }
+        let tolerance = F::of(1e-5) * (max - min).length() + F::of(64.) * F::epsilon() * magnitude;
+        (self.point_at(start) - self.point_at(end)).length() <= tolerance
+    }
+
+    pub fn length(&self) -> F {
+        let (start, end) = self.domain();
+        self.length_between(start, end)
+    }
+
+    /// Length of the curve from parameter `a` to `b`.
+    pub fn length_between(&self, a: F, b: F) -> F {
+        self.pieces(PIECES_PER_SPAN)
+            .windows(2)
+            .map(|piece| (piece[0].max(a), piece[1].min(b)))
+            .filter(|(a, b)| a < b)
+            .map(|(a, b)| self.integrate(a, b))
+            .sum()
+    }
+
+    /// Parameter of the point `length` along the curve from its start, the
+    /// length is clamped to the curve.
+    pub fn parameter_at_length(&self, length: F) -> F {
+        let pieces = self.pieces(PIECES_PER_SPAN);
+        let mut remaining = length.max(F::zero());
+        for piece in pieces.windows(2) {
+            let piece_length = self.integrate(piece[0], piece[1]);
+            if remaining <= piece_length {
+                return self.solve_length(piece[0], piece[1], piece_length, remaining);
+            }
+            remaining -= piece_length;
+        }
+        *pieces.last().unwrap()
+    }
+
+    /// Finds the parameter in `[a, b]` at `target` from `a` by Newton's
+    /// method, falling back to bisection where the curve barely moves.
+    fn solve_length(&self, a: F, b: F, piece_length: F, target: F) -> F {
+        if piece_length <= F::zero() {
+            return a;
+        }
+        let (mut low, mut high) = (a, b);
+        let mut u = a + (b - a) * target / piece_length;
+        for _ in 0..MAX_ITERATIONS {
+            let error = self.integrate(a, u) - target;
+            if error.abs() <= F::of(64.) * F::epsilon() * piece_length {
+                break;
+            }
+            if error > F::zero() {
+                high = u;
+            } else {
+                low = u;
+            }
+            let speed = self.speed(u);
+            let next = u - error / speed;
+            u = if speed > F::epsilon() && low < next && next < high {
+                next
+            } else {
+                (low + high) / F::of(2.)
+            };
+        }
+        u
+    }
+
+    /// Parameters of `count + 1` points at equal distances along the curve,
+    /// including both ends.
+    ///
+    /// The pieces are integrated once and walked in a single pass, each point
+    /// is solved for within the piece it falls in.
+    pub fn divide_by_length(&self, count: usize) -> Vec<F> {
+        let (start, end) = self.domain();
+        if count == 0 {
+            return vec![start];
+        }
+        let pieces = self.pieces(PIECES_PER_SPAN);
+        let lengths: Vec<F> = pieces
+            .windows(2)
+            .map(|piece| self.integrate(piece[0], piece[1]))
+            .collect();
+        let length: F = lengths.iter().copied().sum();
+
+        let mut parameters = vec![start];
+        // Length up to the start of the current piece.
+        let mut walked = F::zero();
+        let mut pieces = pieces.windows(2).zip(lengths);
+        let mut current = pieces.next();
+        for i in 1..count {
+            let target = length * F::of(i as f64 / count as f64);
+            while let Some((_, piece_length)) = current {
+                if target - walked <= piece_length {
+                    break;
+                }
+                walked += piece_length;
+                current = pieces.next();
+            }
+            match current {
+                Some((piece, piece_length)) => parameters.push(self.solve_length(
+                    piece[0],
+                    piece[1],
+                    piece_length,
+                    target - walked,
+                )),
+                None => parameters.push(end),
+            }
+        }
+        parameters.push(end);
+        parameters
+    }
+
+    /// Parameter of the point on the curve closest to `point` and the
+    /// distance to it.
+    pub fn closest_point(&self, point: Vector3D<F, T>) -> (F, F) {
+        let (start, end) = self.domain();
+        if !(point.x.is_finite() && point.y.is_finite() && point.z.is_finite()) {
+            return (start, F::nan());
+        }
+        let closed = self.is_closed();
+        let distance = |u: F| (self.point_at(u) - point).length();
+
+        // Sample densely enough to start close to the global minimum, Newton's
+        // method only finds the nearest local one.
+        let mut best = self
+            .pieces(4 * PIECES_PER_SPAN)
+            .into_iter()
+            .map(|u| (u, distance(u)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+
+        let mut u = best.0;
+        for _ in 0..MAX_ITERATIONS {
+            let ders = self.derivatives(u, 2);
+            let diff = ders[0] - point;
+            let slope = ders[1].dot(diff);
+            let curvature = ders[2].dot(diff) + ders[1].square_length();
+            // Stops at cusps and where the distance is at a maximum.
+            if curvature <= F::epsilon() {
+                break;
+            }
+
+            let mut next = u - slope / curvature;
+            if closed {
+                let period = end - start;
+                let offset = next - start;
+                next = start + offset - period * (offset / period).floor();
+            } else {
+                next = next.clamp(start, end);
+            }
+            if (next - u).abs() < F::of(1e-7) {
+                break;
+            }
+            u = next;
+
+            let d = distance(u);
+            if d < best.1 {
+                best = (u, d);
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::geometry::nurbs::test::circle;
+    use euclid::vec3;
+
+    #[test]
+    fn it_measures_lengths() {
+        let pi = std::f32::consts::PI;
+        let a = circle();
+        assert!((a.length() - 2. * pi).abs() < 1e-4);
+        assert!((a.length_between(0., 0.25) - pi / 2.).abs() < 1e-4);
+
+        for i in 0..=10 {
+            let s = 2. * pi * i as f32 / 10.;
+            let point = a.point_at(a.parameter_at_length(s));
+            assert!((point - vec3(s.cos(), s.sin(), 0.)).length() < 1e-4);
+        }
+
+        let parameters = a.divide_by_length(8);
+        assert_eq!(parameters.len(), 9);
+        for pair in parameters.windows(2) {
+            assert!((a.length_between(pair[0], pair[1]) - pi / 4.).abs() < 1e-4);
+        }
+
+        // Several points per piece and several pieces between points.
+        for &count in &[3, 100] {
+            let parameters = a.divide_by_length(count);
+            assert_eq!(parameters.len(), count + 1);
+            for (i, &u) in parameters.iter().enumerate() {
+                let s = a.length() * i as f32 / count as f32;
+                assert!((u - a.parameter_at_length(s)).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn it_finds_the_closest_point() {
+        let a = circle();
+        assert!(a.is_closed());
+        let (u, distance) = a.closest_point(vec3(2., 2., 0.));
+        assert!((distance - (8_f32.sqrt() - 1.)).abs() < 1e-5);
+        assert!((a.point_at(u) - vec3(0.5_f32.sqrt(), 0.5_f32.sqrt(), 0.)).length() < 1e-4);
+
+        // Across the seam of the closed curve.
+        let (_, distance) = a.closest_point(vec3(1.5 * 0.01_f32.cos(), -1.5 * 0.01_f32.sin(), 0.));
+        assert!((distance - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn it_handles_curves_that_stop() {
+        // The speed drops to zero at the end.
+        let a = Spline::<()>::clamped_uniform(
+            2,
+            vec![
+                (1., vec3(0., 0., 0.)),
+                (1., vec3(1., 0., 0.)),
+                (1., vec3(1., 0., 0.)),
+            ],
+        )
+        .unwrap();
+        assert!(!a.is_closed());
+        assert!((a.length() - 1.).abs() < 1e-4);
+        assert!((a.point_at(a.parameter_at_length(0.5)).x - 0.5).abs() < 1e-4);
+        let (u, distance) = a.closest_point(vec3(2., 1., 0.));
+        assert!((u - 1.).abs() < 1e-3);
+        assert!((distance - 2_f32.sqrt()).abs() < 1e-4);
+    }
+
+    #[test]
+    fn it_scales_tolerances_to_the_curve() {
+        let small = |gap: f64| {
+            Spline::<(), f64>::clamped_uniform(
+                2,
+                vec![
+                    (1., vec3(0., 0., 0.)),
+                    (1., vec3(1e-3, 0., 0.)),
+                    (1., vec3(1e-3, 1e-3, 0.)),
+                    (1., vec3(gap, 0., 0.)),
+                ],
+            )
+            .unwrap()
+        };
+        assert!(small(0.).is_closed());
+        assert!(!small(1e-6).is_closed());
+
+        // Far from the origin, rounding alone opens an `f32` curve.
+        let far = Spline::<()>::clamped_uniform(
+            2,
+            vec![
+                (1., vec3(1e4, 1e4, 0.)),
+                (1., vec3(1e4 + 1., 1e4, 0.)),
+                (1., vec3(1e4 + 1., 1e4 + 1., 0.)),
+                (1., vec3(1e4, 1e4, 0.)),
+            ],
+        )
+        .unwrap();
+        assert!(far.is_closed());
+
+        let a = small(0.);
+        let length = a.length();
+        for i in 0..=10 {
+            let s = length * i as f64 / 10.;
+            let u = a.parameter_at_length(s);
+            assert!((a.length_between(0., u) - s).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn it_ignores_queries_off_the_numbers() {
+        let a = circle();
+        let (u, distance) = a.closest_point(vec3(f32::NAN, 0., 0.));
+        assert_eq!(u, 0.);
+        assert!(distance.is_nan());
+    }
+}
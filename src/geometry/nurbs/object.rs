@@ -0,0 +1,426 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use euclid::{Box2D, Point2D, Transform2D, Vector3D};
+use glium::backend::{Context, Facade};
+use glium::index::{IndexBuffer, PrimitiveType};
+use glium::vertex::BufferCreationError;
+use glium::VertexBuffer;
+
+use super::{Spline, Surface, SurfaceMesh};
+use crate::canvas::{union_bounds, CanvasError, CanvasObject, DrawingContext};
+use crate::geometry::{CanvasSpace, Line as GLine, ModelSpace, Scalar};
+use crate::graphics::dyn_vertex_buffer::DynVertexBuffer;
+use crate::graphics::primitives::{Color, ColoredPoint, Line, LinePoint, Vf2};
+use crate::grid::pixels_per_unit;
+
+/// The tessellation is made for this much more zoom than the current one and
+/// kept while the zoom stays within the same factor either way.
+const ZOOM_FACTOR: f32 = 1.5;
+
+/// Draws a [`Spline`] as a line strip, tessellated adaptively for the current
+/// zoom and cached until the curve or the zoom changes. For editing it can
+/// also show the control polygon and the knots.
+///
+/// The curve is drawn relative to [`DrawingContext::model_origin`], so curves
+/// in `f64` stay precise far away from the origin of the model.
+pub struct SplineObject<F = f32> {
+    context: Rc<Context>,
+    spline: Spline<ModelSpace, F>,
+    /// Largest distance between the drawn line and the curve, in pixels.
+    pub tolerance: f32,
+    pub color: Color,
+    /// Width of the curve in pixels.
+    pub width: f32,
+    /// Draws the control polygon and the control points, sized by their
+    /// weight.
+    pub show_control_polygon: bool,
+    /// Marks the knots on the curve.
+    pub show_knots: bool,
+    pub control_color: Color,
+    /// Size of control points with weight 1 and of knot markers, in pixels.
+    pub handle_size: f32,
+    tessellation: RefCell<Tessellation<F>>,
+}
+
+struct Tessellation<F> {
+    points: Vec<Point2D<F, ModelSpace>>,
+    buffer: DynVertexBuffer<LinePoint>,
+    /// Pixels per model unit it was made for, `None` after the curve changed.
+    pixels_per_unit: Option<f32>,
+    /// The points in `buffer` are relative to it.
+    origin: Point2D<F, ModelSpace>,
+}
+
+impl<F: Scalar> SplineObject<F> {
+    pub fn new(
+        facade: &impl Facade,
+        spline: Spline<ModelSpace, F>,
+    ) -> Result<SplineObject<F>, BufferCreationError> {
+        Ok(SplineObject {
+            context: facade.get_context().clone(),
+            spline,
+            tolerance: 0.25,
+            color: Color::BLUE,
+            width: 1.,
+            show_control_polygon: false,
+            show_knots: false,
+            control_color: Color::GRAY,
+            handle_size: 6.,
+            tessellation: RefCell::new(Tessellation {
+                points: Vec::new(),
+                buffer: DynVertexBuffer::new(facade)?,
+                pixels_per_unit: None,
+                origin: Point2D::origin(),
+            }),
+        })
+    }
+
+    pub fn spline(&self) -> &Spline<ModelSpace, F> {
+        &self.spline
+    }
+
+    pub fn set_spline(&mut self, spline: Spline<ModelSpace, F>) {
+        self.spline = spline;
+        self.tessellation.get_mut().pixels_per_unit = None;
+    }
+
+    /// Changes the curve in place, it is tessellated again when drawn next.
+    pub fn spline_mut(&mut self) -> &mut Spline<ModelSpace, F> {
+        self.tessellation.get_mut().pixels_per_unit = None;
+        &mut self.spline
+    }
+
+    /// Tessellates again if the zoom left the range of the current
+    /// tessellation, and uploads it again if the origin moved.
+    fn update_tessellation(
+        &self,
+        pixels_per_unit: f32,
+        origin: Point2D<F, ModelSpace>,
+    ) -> Result<(), CanvasError> {
+        let mut tessellation = self.tessellation.borrow_mut();
+        let made_for = (tessellation.pixels_per_unit, tessellation.origin);
+        match needed_update(made_for, pixels_per_unit, origin) {
+            Update::None => return Ok(()),
+            Update::Upload => {}
+            Update::Tessellate => {
+                let ppu = pixels_per_unit * ZOOM_FACTOR;
+                let tolerance = F::of((self.tolerance / ppu) as f64);
+                tessellation.points = self
+                    .spline
+                    .tessellate(tolerance)
+                    .into_iter()
+                    .map(to_point)
+                    .collect();
+                tessellation.pixels_per_unit = Some(ppu);
+            }
+        }
+
+        let points: Vec<_> = tessellation
+            .points
+            .iter()
+            .map(|&p| LinePoint::from_relative(p, origin))
+            .collect();
+        tessellation.buffer.write(&self.context, &points)?;
+        tessellation.origin = origin;
+        Ok(())
+    }
+
+    fn draw_controls(&self, ctx: &DrawingContext, origin: Point2D<F, ModelSpace>) {
+        let polygon: Vec<_> = self
+            .spline
+            .control_points()
+            .iter()
+            .map(|&(_, p)| LinePoint::from_relative(to_point(p), origin))
+            .collect();
+        ctx.batch_line_strip(&polygon, self.control_color, 1. / ctx.pixels_per_unit());
+
+        // Point sizes are in canvas units.
+        let canvas_ppu = pixels_per_unit(ctx.view_transform, ctx.dimensions.0);
+        for &(weight, p) in self.spline.control_points() {
+            let point = ColoredPoint {
+                vertex: Vf2::relative(to_point(p), origin),
+                color: self.control_color,
+            };
+            let size = self.handle_size * weight.as_f32().sqrt().clamp(0.5, 3.);
+            ctx.batch_colored_points(&[point], size / canvas_ppu);
+        }
+    }
+
+    fn draw_knots(&self, ctx: &DrawingContext, origin: Point2D<F, ModelSpace>) {
+        let half_size = F::of((self.handle_size / 2. / ctx.pixels_per_unit()) as f64);
+        let (start, end) = self.spline.domain();
+        let mut knots = self.spline.knots().to_vec();
+        knots.retain(|&u| start <= u && u <= end);
+        knots.dedup();
+
+        let markers: Vec<_> = knots
+            .into_iter()
+            .map(|u| {
+                (
+                    self.spline.point_at(u),
+                    self.spline.normal_at(u) * half_size,
+                )
+            })
+            .filter(|(_, normal)| normal.x.is_finite() && normal.y.is_finite())
+            .map(|(p, n)| {
+                let marker = GLine {
+                    start: to_point(p - n),
+                    end: to_point(p + n),
+                };
+                Line::from_relative(&marker, origin)
+            })
+            .collect();
+        ctx.batch_lines(&markers, self.control_color, 1. / ctx.pixels_per_unit());
+    }
+}
+
+impl<F: Scalar> CanvasObject for SplineObject<F> {
+    fn draw<'a>(&self, ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+        let origin = model_origin(ctx);
+        self.update_tessellation(ctx.pixels_per_unit(), origin)?;
+        ctx.with_model_transform(origin_transform(origin), |mut ctx| {
+            let width = self.width / ctx.pixels_per_unit();
+            let model_transform = ctx.model_transform;
+            ctx.programs.draw_line_strips(
+                ctx.target()?,
+                self.tessellation.borrow().buffer.get(),
+                self.color,
+                width,
+                model_transform,
+            )?;
+
+            if self.show_control_polygon {
+                self.draw_controls(&ctx, origin);
+            }
+            if self.show_knots {
+                self.draw_knots(&ctx, origin);
+            }
+            Ok(())
+        })
+    }
+
+    /// The control polygon, which contains the curve.
+    fn bounds(&self) -> Option<Box2D<f32, ModelSpace>> {
+        union_bounds(self.spline.control_points().iter().map(|&(_, p)| {
+            let p = to_point(p).map(F::as_f32);
+            Box2D::new(p, p)
+        }))
+    }
+}
+
+/// Draws a [`Surface`] as a triangle mesh projected on the xy plane, colored
+/// by the parameters of its points. The mesh is cached until the surface
+/// changes, and its upload until the coloring or the origin changes. Like
+/// [`SplineObject`] it is drawn relative to the model origin.
+pub struct SurfaceObject<F = f32> {
+    context: Rc<Context>,
+    surface: Surface<ModelSpace, F>,
+    steps_per_span: usize,
+    color_map: Box<dyn Fn(f32, f32) -> Color>,
+    mesh: RefCell<Option<SurfaceMesh<ModelSpace, F>>>,
+    buffers: RefCell<Option<MeshBuffers<F>>>,
+}
+
+/// The mesh in buffers, relative to `origin`.
+struct MeshBuffers<F> {
+    vertices: VertexBuffer<ColoredPoint>,
+    indices: IndexBuffer<u32>,
+    origin: Point2D<F, ModelSpace>,
+}
+
+impl<F: Scalar> SurfaceObject<F> {
+    /// The default colors blend from blue at `(0, 0)` over red and green to
+    /// yellow at `(1, 1)`.
+    pub fn new(facade: &impl Facade, surface: Surface<ModelSpace, F>) -> SurfaceObject<F> {
+        SurfaceObject {
+            context: facade.get_context().clone(),
+            surface,
+            steps_per_span: 8,
+            color_map: Box::new(|u, v| {
+                Color::BLUE
+                    .mix(Color::RED, u)
+                    .mix(Color::GREEN.mix(Color::YELLOW, u), v)
+            }),
+            mesh: RefCell::new(None),
+            buffers: RefCell::new(None),
+        }
+    }
+
+    pub fn surface(&self) -> &Surface<ModelSpace, F> {
+        &self.surface
+    }
+
+    pub fn set_surface(&mut self, surface: Surface<ModelSpace, F>) {
+        self.surface = surface;
+        *self.mesh.get_mut() = None;
+        *self.buffers.get_mut() = None;
+    }
+
+    /// Cells of the mesh per knot span in each direction.
+    pub fn set_steps_per_span(&mut self, steps: usize) {
+        self.steps_per_span = steps;
+        *self.mesh.get_mut() = None;
+        *self.buffers.get_mut() = None;
+    }
+
+    /// Colors the points of the surface by their `(u, v)`.
+    pub fn set_color_map(&mut self, color_map: impl Fn(f32, f32) -> Color + 'static) {
+        self.color_map = Box::new(color_map);
+        *self.buffers.get_mut() = None;
+    }
+
+    fn upload_mesh(
+        &self,
+        mesh: &SurfaceMesh<ModelSpace, F>,
+        origin: Point2D<F, ModelSpace>,
+    ) -> Result<MeshBuffers<F>, CanvasError> {
+        let vertices: Vec<_> = mesh
+            .points
+            .iter()
+            .zip(&mesh.parameters)
+            .map(|(&p, &(u, v))| ColoredPoint {
+                vertex: Vf2::relative(to_point(p), origin),
+                color: (self.color_map)(u.as_f32(), v.as_f32()),
+            })
+            .collect();
+        let indices: Vec<u32> = mesh.triangles.concat();
+        Ok(MeshBuffers {
+            vertices: VertexBuffer::new(&self.context, &vertices)?,
+            indices: IndexBuffer::new(&self.context, PrimitiveType::TrianglesList, &indices)?,
+            origin,
+        })
+    }
+}
+
+impl<F: Scalar> CanvasObject for SurfaceObject<F> {
+    fn draw<'a>(&self, ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+        let origin = model_origin(ctx);
+        let mut buffers = self.buffers.borrow_mut();
+        if buffers
+            .as_ref()
+            .map_or(true, |buffers| buffers.origin != origin)
+        {
+            let mut mesh = self.mesh.borrow_mut();
+            let mesh = mesh.get_or_insert_with(|| self.surface.tessellate(self.steps_per_span));
+            *buffers = Some(self.upload_mesh(mesh, origin)?);
+        }
+        let buffers = buffers.as_ref().unwrap();
+        ctx.with_model_transform(origin_transform(origin), |mut ctx| {
+            let model_transform = ctx.model_transform;
+            ctx.programs.draw_mesh(
+                ctx.target()?,
+                buffers.vertices.slice(..).unwrap(),
+                buffers.indices.slice(..).unwrap(),
+                model_transform,
+            )?;
+            Ok(())
+        })
+    }
+
+    /// The control grid, which contains the surface.
+    fn bounds(&self) -> Option<Box2D<f32, ModelSpace>> {
+        union_bounds(
+            self.surface
+                .control_points()
+                .iter()
+                .flatten()
+                .map(|&(_, p)| {
+                    let p = to_point(p).map(F::as_f32);
+                    Box2D::new(p, p)
+                }),
+        )
+    }
+}
+
+/// What a cached tessellation needs before it can be drawn.
+#[derive(Debug, PartialEq)]
+enum Update {
+    None,
+    /// Only the origin moved, the points are uploaded relative to the new one.
+    Upload,
+    Tessellate,
+}
+
+/// Decides what to redo for a tessellation `made_for` some pixels per unit,
+/// `None` after the curve changed, and uploaded relative to an origin.
+/// Made for [`ZOOM_FACTOR`] times the zoom of the time, it is kept while the
+/// zoom stays within that factor of it in or out.
+fn needed_update<O: PartialEq>(
+    made_for: (Option<f32>, O),
+    pixels_per_unit: f32,
+    origin: O,
+) -> Update {
+    match made_for {
+        (Some(ppu), made_at)
+            if ppu / (ZOOM_FACTOR * ZOOM_FACTOR) <= pixels_per_unit && pixels_per_unit <= ppu =>
+        {
+            if made_at == origin {
+                Update::None
+            } else {
+                Update::Upload
+            }
+        }
+        _ => Update::Tessellate,
+    }
+}
+
+/// The origin of `ctx` in the precision of the geometry.
+fn model_origin<F: Scalar>(ctx: &DrawingContext) -> Point2D<F, ModelSpace> {
+    ctx.model_origin().map(F::of)
+}
+
+/// Moves what is drawn relative to `origin` to its place in the model, for
+/// [`DrawingContext::with_model_transform`].
+fn origin_transform<F: Scalar>(
+    origin: Point2D<F, ModelSpace>,
+) -> Transform2D<f64, ModelSpace, CanvasSpace> {
+    Transform2D::translation(origin.x.as_f64(), origin.y.as_f64())
+}
+
+fn to_point<F, U>(p: Vector3D<F, U>) -> Point2D<F, U> {
+    Point2D::new(p.x, p.y)
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn it_keeps_tessellations_while_zooming_a_little() {
+        let made_for = (Some(10. * ZOOM_FACTOR), 0);
+        assert_eq!(needed_update(made_for, 10., 0), Update::None);
+        // The same factor in and out of the zoom it was made at.
+        assert_eq!(needed_update(made_for, 10. * ZOOM_FACTOR, 0), Update::None);
+        assert_eq!(needed_update(made_for, 10. / ZOOM_FACTOR, 0), Update::None);
+        assert_eq!(needed_update(made_for, 16., 0), Update::Tessellate);
+        assert_eq!(needed_update(made_for, 6., 0), Update::Tessellate);
+
+        // Zooming out a hundredfold step by step tessellates once per factor.
+        let mut made_for = (None, 0);
+        let mut tessellated = 0;
+        let mut ppu = 100.;
+        while ppu > 1. {
+            if needed_update(made_for, ppu, 0) == Update::Tessellate {
+                made_for.0 = Some(ppu * ZOOM_FACTOR);
+                tessellated += 1;
+            }
+            ppu *= 0.99;
+        }
+        let factors = (100_f32.ln() / ZOOM_FACTOR.ln()).ceil() as usize;
+        assert!(tessellated <= factors, "{}", tessellated);
+    }
+
+    #[test]
+    fn it_uploads_again_when_the_origin_moves() {
+        let at = |x| Point2D::<f64, ModelSpace>::new(x, 0.);
+        let made_for = (Some(10.), at(0.));
+        assert_eq!(needed_update(made_for, 10., at(0.)), Update::None);
+        assert_eq!(needed_update(made_for, 10., at(64.)), Update::Upload);
+        assert_eq!(
+            needed_update((None, at(0.)), 10., at(64.)),
+            Update::Tessellate
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Tensor product surfaces, evaluated with the same basis functions as the
+//! curves in each direction.
+
+use euclid::Vector3D;
+
+use super::{basis_its_0, clamped_uniform_knots, find_span, Spline, SplineError};
+use crate::geometry::Scalar;
+
+/// Represents a nurbs surface over the parameters `(u, v)`.
+///
+/// Like the curve knots, both knot vectors are normalized (range 0 to 1).
+#[derive(Clone, Debug)]
+pub struct Surface<T, F = f32> {
+    degree_u: usize,
+    degree_v: usize,
+    /// `c_points[i][j]` is the `i`-th control point along u and the `j`-th
+    /// along v.
+    c_points: Vec<Vec<(F, Vector3D<F, T>)>>,
+    knots_u: Vec<F>,
+    knots_v: Vec<F>,
+}
+
+/// Triangles approximating a [`Surface`], made by [`Surface::tessellate`].
+#[derive(Clone, Debug)]
+pub struct SurfaceMesh<T, F = f32> {
+    pub points: Vec<Vector3D<F, T>>,
+    /// The `(u, v)` of every point.
+    pub parameters: Vec<(F, F)>,
+    /// Indices into `points`.
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl<T, F: Scalar> Surface<T, F> {
+    /// Creates a surface from a grid of weighted control points, given as
+    /// `(weight, point)`, and normalized knots in both directions. Every
+    /// row and column has to make up a valid curve, see [`Spline::new`].
+    pub fn new(
+        degree_u: usize,
+        degree_v: usize,
+        c_points: Vec<Vec<(F, Vector3D<F, T>)>>,
+        knots_u: Vec<F>,
+        knots_v: Vec<F>,
+    ) -> Result<Surface<T, F>, SplineError> {
+        let columns = c_points.first().map_or(0, |row| row.len());
+        if let Some(row) = c_points.iter().position(|row| row.len() != columns) {
+            return Err(SplineError::RaggedControlGrid { row });
+        }
+
+        // The weights of all points are checked with the rows, the first
+        // column is enough for the knots along u.
+        for row in &c_points {
+            Spline::new(degree_v, row.clone(), knots_v.clone())?;
+        }
+        let column = c_points.iter().map(|row| row[0]).collect();
+        Spline::new(degree_u, column, knots_u.clone())?;
+
+        Ok(Surface {
+            degree_u,
+            degree_v,
+            c_points,
+            knots_u,
+            knots_v,
+        })
+    }
+
+    /// A clamped surface with uniform knots, its corners are the corners of
+    /// the control grid.
+    pub fn clamped_uniform(
+        degree_u: usize,
+        degree_v: usize,
+        c_points: Vec<Vec<(F, Vector3D<F, T>)>>,
+    ) -> Result<Surface<T, F>, SplineError> {
+        let knots_u = clamped_uniform_knots(degree_u, c_points.len());
+        let knots_v = clamped_uniform_knots(degree_v, c_points.first().map_or(0, |row| row.len()));
+        Surface::new(degree_u, degree_v, c_points, knots_u, knots_v)
+    }
+
+    pub fn degrees(&self) -> (usize, usize) {
+        (self.degree_u, self.degree_v)
+    }
+
+    pub fn control_points(&self) -> &[Vec<(F, Vector3D<F, T>)>] {
+        &self.c_points
+    }
+
+    pub fn knots_u(&self) -> &[F] {
+        &self.knots_u
+    }
+
+    pub fn knots_v(&self) -> &[F] {
+        &self.knots_v
+    }
+
+    /// Parameter ranges of the surface along u and v.
+    pub fn domain(&self) -> ((F, F), (F, F)) {
+        let (count_u, count_v) = (self.c_points.len(), self.c_points[0].len());
+        (
+            (self.knots_u[self.degree_u], self.knots_u[count_u]),
+            (self.knots_v[self.degree_v], self.knots_v[count_v]),
+        )
+    }
+
+    /// The point at `(u, v)`, both are clamped to the domain.
+    pub fn point_at(&self, u: F, v: F) -> Vector3D<F, T> {
+        let ((u_start, u_end), (v_start, v_end)) = self.domain();
+        let (u, v) = (u.clamp(u_start, u_end), v.clamp(v_start, v_end));
+        let (p, q) = (self.degree_u, self.degree_v);
+        let ku = find_span(&self.knots_u, p, self.c_points.len(), u);
+        let kv = find_span(&self.knots_v, q, self.c_points[0].len(), v);
+
+        let size = p.max(q) + 1;
+        let (mut ls, mut rs) = (vec![F::zero(); size], vec![F::zero(); size]);
+        let mut bases_u = vec![F::zero(); p + 1];
+        let mut bases_v = vec![F::zero(); q + 1];
+        basis_its_0(&self.knots_u, p, ku, u, &mut bases_u, &mut ls, &mut rs);
+        basis_its_0(&self.knots_v, q, kv, v, &mut bases_v, &mut ls, &mut rs);
+
+        let mut bases_sum = F::zero();
+        let mut point = Vector3D::zero();
+        for (i, &base_u) in bases_u.iter().enumerate() {
+            let row = &self.c_points[ku - p + i][kv - q..=kv];
+            for (&(weight, c_point), &base_v) in row.iter().zip(&bases_v) {
+                let base = base_u * base_v * weight;
+                bases_sum += base;
+                point += c_point * base;
+            }
+        }
+        point / bases_sum
+    }
+
+    /// Triangles through the points of a grid with `steps_per_span` cells per
+    /// knot span in both directions. The grid lines follow the knots, so
+    /// creases of the surface fall on edges of the mesh.
+    pub fn tessellate(&self, steps_per_span: usize) -> SurfaceMesh<T, F> {
+        let (count_u, count_v) = (self.c_points.len(), self.c_points[0].len());
+        let us = span_parameters(&self.knots_u, self.degree_u, count_u, steps_per_span);
+        let vs = span_parameters(&self.knots_v, self.degree_v, count_v, steps_per_span);
+
+        let mut points = Vec::with_capacity(us.len() * vs.len());
+        let mut parameters = Vec::with_capacity(us.len() * vs.len());
+        for &u in &us {
+            for &v in &vs {
+                points.push(self.point_at(u, v));
+                parameters.push((u, v));
+            }
+        }
+
+        let index = |i: usize, j: usize| (i * vs.len() + j) as u32;
+        let mut triangles = Vec::with_capacity(2 * (us.len() - 1) * (vs.len() - 1));
+        for i in 0..us.len() - 1 {
+            for j in 0..vs.len() - 1 {
+                triangles.push([index(i, j), index(i + 1, j), index(i + 1, j + 1)]);
+                triangles.push([index(i, j), index(i + 1, j + 1), index(i, j + 1)]);
+            }
+        }
+
+        SurfaceMesh {
+            points,
+            parameters,
+            triangles,
+        }
+    }
+}
+
+/// Parameters splitting every nonempty knot span of the domain into `steps`
+/// equal parts, including the knots.
+fn span_parameters<F: Scalar>(knots: &[F], degree: usize, count: usize, steps: usize) -> Vec<F> {
+    let steps = steps.max(1);
+    let mut knots = knots[degree..=count].to_vec();
+    knots.dedup();
+    let mut parameters = vec![knots[0]];
+    for span in knots.windows(2) {
+        for i in 1..=steps {
+            parameters.push(span[0] + (span[1] - span[0]) * F::of(i as f64 / steps as f64));
+        }
+    }
+    parameters
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use euclid::vec3;
+
+    #[test]
+    fn it_evaluates_a_bilinear_patch() {
+        let a = Surface::<()>::clamped_uniform(
+            1,
+            1,
+            vec![
+                vec![(1., vec3(0., 0., 0.)), (1., vec3(0., 2., 0.))],
+                vec![(1., vec3(1., 0., 0.)), (1., vec3(3., 2., 1.))],
+            ],
+        )
+        .unwrap();
+        for &(u, v) in &[(0., 0.), (1., 1.), (0.5, 0.5), (0.25, 0.75)] {
+            let expected = vec3(u + 2. * u * v, 2. * v, u * v);
+            assert!((a.point_at(u, v) - expected).length() < 1e-6);
+        }
+
+        let mesh = a.tessellate(4);
+        assert_eq!(mesh.points.len(), 25);
+        assert_eq!(mesh.triangles.len(), 32);
+        assert_eq!(mesh.parameters[24], (1., 1.));
+        assert_eq!(mesh.points[24], vec3(3., 2., 1.));
+    }
+
+    #[test]
+    fn it_evaluates_a_rational_patch() {
+        // A quarter of an annulus, exact circles along u and lines along v.
+        let w = 0.5_f32.sqrt();
+        let c_points = [(1., 1., 0.), (w, 1., 1.), (1., 0., 1.)]
+            .iter()
+            .map(|&(weight, x, y)| {
+                vec![(weight, vec3(x, y, 0.)), (weight, vec3(2. * x, 2. * y, 0.))]
+            })
+            .collect();
+        let a = Surface::<()>::clamped_uniform(2, 1, c_points).unwrap();
+        for point in a.tessellate(8).points {
+            let radius = point.length();
+            assert!((1. - 1e-5..=2. + 1e-5).contains(&radius));
+        }
+        for i in 0..=10 {
+            let u = i as f32 / 10.;
+            assert!((a.point_at(u, 0.).length() - 1.).abs() < 1e-5);
+            assert!((a.point_at(u, 0.5).length() - 1.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn it_validates_the_grid() {
+        let row = vec![(1., vec3(0., 0., 0.)), (1., vec3(1., 0., 0.))];
+        assert_eq!(
+            Surface::<()>::clamped_uniform(1, 1, vec![row.clone(), row[..1].to_vec()]).unwrap_err(),
+            SplineError::RaggedControlGrid { row: 1 }
+        );
+        assert_eq!(
+            Surface::<()>::clamped_uniform(2, 1, vec![row.clone(), row.clone()]).unwrap_err(),
+            SplineError::InvalidDegree {
+                degree: 2,
+                points: 2
+            }
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use euclid::{Box2D, Point2D};
+
+/// Nodes holding more items than this are split, unless they are too deep.
+const NODE_CAPACITY: usize = 8;
+const MAX_DEPTH: usize = 16;
+
+/// Quadtree over bounding boxes, for picking objects and finding the ones in
+/// a rectangle, e.g. the visible part of the canvas.
+///
+/// Every item is stored in the smallest node that fully contains its bounds.
+/// Items outside of the bounds of the tree are kept in the root, they work
+/// but are checked on every query.
+pub struct QuadTree<T, S> {
+    root: Node<T, S>,
+    len: usize,
+}
+
+struct Node<T, S> {
+    bounds: Box2D<f32, S>,
+    items: Vec<(Box2D<f32, S>, T)>,
+    children: Option<Box<[Node<T, S>; 4]>>,
+}
+
+impl<T, S> QuadTree<T, S> {
+    pub fn new(bounds: Box2D<f32, S>) -> QuadTree<T, S> {
+        QuadTree {
+            root: Node::new(bounds),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.root = Node::new(self.root.bounds);
+        self.len = 0;
+    }
+
+    pub fn insert(&mut self, bounds: Box2D<f32, S>, item: T) {
+        self.root.insert(bounds, item, 0);
+        self.len += 1;
+    }
+
+    /// Removes the items with the given bounds for which `f` returns true.
+    pub fn remove(&mut self, bounds: &Box2D<f32, S>, mut f: impl FnMut(&T) -> bool) -> Vec<T> {
+        let removed = self.root.remove(bounds, &mut f);
+        self.len -= removed.len();
+        removed
+    }
+
+    /// Items whose bounds intersect `rect`, boxes touching it included.
+    pub fn query_rect(&self, rect: &Box2D<f32, S>) -> Vec<&T> {
+        let mut result = Vec::new();
+        self.root.query_rect(rect, &mut result);
+        result
+    }
+
+    /// The item closest to `point` within `max_distance`, together with its
+    /// distance. Items are measured by their bounding box unless `distance`
+    /// returns something more exact, which must never be closer than the box.
+    pub fn nearest(
+        &self,
+        point: Point2D<f32, S>,
+        max_distance: f32,
+        mut distance: impl FnMut(&T) -> Option<f32>,
+    ) -> Option<(&T, f32)> {
+        // Best first search, nodes and items are visited by the distance to
+        // their bounds, so the first item taken from the heap with its exact
+        // distance is the closest one.
+        let mut heap = BinaryHeap::new();
+        heap.push(Candidate {
+            distance: box_distance(&self.root.bounds, point).min(root_outliers(&self.root, point)),
+            entry: Entry::Node(&self.root),
+        });
+
+        while let Some(Candidate { distance: d, entry }) = heap.pop() {
+            if d > max_distance {
+                return None;
+            }
+            match entry {
+                Entry::Exact(item) => return Some((item, d)),
+                Entry::Item(item) => {
+                    let exact = distance(item).unwrap_or(d);
+                    heap.push(Candidate {
+                        distance: exact.max(d),
+                        entry: Entry::Exact(item),
+                    });
+                }
+                Entry::Node(node) => {
+                    for (bounds, item) in &node.items {
+                        heap.push(Candidate {
+                            distance: box_distance(bounds, point),
+                            entry: Entry::Item(item),
+                        });
+                    }
+                    for child in node.children.iter().flat_map(|c| c.iter()) {
+                        heap.push(Candidate {
+                            distance: box_distance(&child.bounds, point),
+                            entry: Entry::Node(child),
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The root also holds items outside of its bounds, so it can't be skipped
+/// based on its bounds alone.
+fn root_outliers<T, S>(root: &Node<T, S>, point: Point2D<f32, S>) -> f32 {
+    root.items
+        .iter()
+        .map(|(bounds, _)| box_distance(bounds, point))
+        .fold(f32::INFINITY, f32::min)
+}
+
+/// Distance from `point` to the closest point of `bounds`, 0 inside of it.
+pub fn box_distance<S>(bounds: &Box2D<f32, S>, point: Point2D<f32, S>) -> f32 {
+    let dx = (bounds.min.x - point.x).max(point.x - bounds.max.x).max(0.);
+    let dy = (bounds.min.y - point.y).max(point.y - bounds.max.y).max(0.);
+    dx.hypot(dy)
+}
+
+fn contains_box<S>(outer: &Box2D<f32, S>, inner: &Box2D<f32, S>) -> bool {
+    outer.min.x <= inner.min.x
+        && outer.min.y <= inner.min.y
+        && inner.max.x <= outer.max.x
+        && inner.max.y <= outer.max.y
+}
+
+pub(crate) fn touches<S>(a: &Box2D<f32, S>, b: &Box2D<f32, S>) -> bool {
+    a.min.x <= b.max.x && b.min.x <= a.max.x && a.min.y <= b.max.y && b.min.y <= a.max.y
+}
+
+impl<T, S> Node<T, S> {
+    fn new(bounds: Box2D<f32, S>) -> Node<T, S> {
+        Node {
+            bounds,
+            items: Vec::new(),
+            children: None,
+        }
+    }
+
+    fn insert(&mut self, bounds: Box2D<f32, S>, item: T, depth: usize) {
+        if let Some(children) = &mut self.children {
+            if let Some(child) = children
+                .iter_mut()
+                .find(|c| contains_box(&c.bounds, &bounds))
+            {
+                return child.insert(bounds, item, depth + 1);
+            }
+            self.items.push((bounds, item));
+            return;
+        }
+
+        self.items.push((bounds, item));
+        if self.items.len() > NODE_CAPACITY && depth < MAX_DEPTH {
+            self.split(depth);
+        }
+    }
+
+    fn split(&mut self, depth: usize) {
+        let Box2D { min, max } = self.bounds;
+        let center = self.bounds.center();
+        self.children = Some(Box::new([
+            Node::new(Box2D::new(min, center)),
+            Node::new(Box2D::new(
+                Point2D::new(center.x, min.y),
+                Point2D::new(max.x, center.y),
+            )),
+            Node::new(Box2D::new(
+                Point2D::new(min.x, center.y),
+                Point2D::new(center.x, max.y),
+            )),
+            Node::new(Box2D::new(center, max)),
+        ]));
+        for (bounds, item) in std::mem::take(&mut self.items) {
+            self.insert(bounds, item, depth);
+        }
+    }
+
+    fn remove(&mut self, bounds: &Box2D<f32, S>, f: &mut impl FnMut(&T) -> bool) -> Vec<T> {
+        let mut removed = Vec::new();
+        let mut i = 0;
+        while i < self.items.len() {
+            let (b, item) = &self.items[i];
+            if b == bounds && f(item) {
+                removed.push(self.items.swap_remove(i).1);
+            } else {
+                i += 1;
+            }
+        }
+        if let Some(children) = &mut self.children {
+            for child in children.iter_mut() {
+                if contains_box(&child.bounds, bounds) {
+                    removed.extend(child.remove(bounds, f));
+                }
+            }
+        }
+        removed
+    }
+
+    fn query_rect<'a>(&'a self, rect: &Box2D<f32, S>, result: &mut Vec<&'a T>) {
+        result.extend(
+            self.items
+                .iter()
+                .filter(|(bounds, _)| touches(bounds, rect))
+                .map(|(_, item)| item),
+        );
+        for child in self.children.iter().flat_map(|c| c.iter()) {
+            if touches(&child.bounds, rect) {
+                child.query_rect(rect, result);
+            }
+        }
+    }
+}
+
+enum Entry<'a, T, S> {
+    Node(&'a Node<T, S>),
+    Item(&'a T),
+    Exact(&'a T),
+}
+
+struct Candidate<'a, T, S> {
+    distance: f32,
+    entry: Entry<'a, T, S>,
+}
+
+impl<'a, T, S> PartialEq for Candidate<'a, T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'a, T, S> Eq for Candidate<'a, T, S> {}
+
+impl<'a, T, S> PartialOrd for Candidate<'a, T, S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a, T, S> Ord for Candidate<'a, T, S> {
+    /// Reversed, so the heap pops the closest candidate first. On equal
+    /// distances exact items come first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        let exact = |c: &Self| matches!(c.entry, Entry::Exact(_));
+        other
+            .distance
+            .partial_cmp(&self.distance)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| exact(self).cmp(&exact(other)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::geometry::test::generator;
+    use crate::geometry::CanvasSpace;
+
+    fn boxes() -> Vec<Box2D<f32, CanvasSpace>> {
+        // Deterministic scatter of small and some large boxes.
+        let mut random = generator(12345);
+        let mut next = move || random() as f32;
+        (0..500)
+            .map(|i| {
+                let p = Point2D::new(next() * 220. - 110., next() * 220. - 110.);
+                let size = if i % 50 == 0 { 40. } else { next() * 3. };
+                Box2D::new(p, p + euclid::Vector2D::new(size, size * 0.5))
+            })
+            .collect()
+    }
+
+    fn tree() -> QuadTree<usize, CanvasSpace> {
+        let mut tree = QuadTree::new(Box2D::new(
+            Point2D::new(-100., -100.),
+            Point2D::new(100., 100.),
+        ));
+        for (i, b) in boxes().into_iter().enumerate() {
+            tree.insert(b, i);
+        }
+        tree
+    }
+
+    #[test]
+    fn it_finds_the_boxes_in_a_rect() {
+        let tree = tree();
+        let rect = Box2D::new(Point2D::new(-20., 5.), Point2D::new(30., 60.));
+
+        let mut found: Vec<_> = tree.query_rect(&rect).into_iter().copied().collect();
+        found.sort_unstable();
+        let expected: Vec<_> = (0..500).filter(|i| touches(&boxes()[*i], &rect)).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn it_finds_the_nearest_box() {
+        let tree = tree();
+        let boxes = boxes();
+        for point in &[
+            Point2D::new(0., 0.),
+            Point2D::new(-105., 93.),
+            Point2D::new(300., 0.),
+        ] {
+            let (found, distance) = tree.nearest(*point, f32::INFINITY, |_| None).unwrap();
+            let expected = boxes
+                .iter()
+                .map(|b| box_distance(b, *point))
+                .fold(f32::INFINITY, f32::min);
+            assert_eq!(distance, expected);
+            assert_eq!(box_distance(&boxes[*found], *point), expected);
+        }
+
+        assert!(tree
+            .nearest(Point2D::new(500., 500.), 10., |_| None)
+            .is_none());
+    }
+
+    #[test]
+    fn it_removes_items() {
+        let mut tree = tree();
+        let boxes = boxes();
+        assert_eq!(tree.remove(&boxes[7], |i| *i == 7), vec![7]);
+        assert_eq!(tree.len(), 499);
+        assert!(!tree.query_rect(&boxes[7]).contains(&&7));
+    }
+}
@@ -0,0 +1,345 @@
+use std::rc::Rc;
+
+use euclid::{Point2D, Transform2D};
+use glium::backend::{Context, Facade};
+use glium::vertex::{Vertex, VertexBufferSlice};
+use glium::{DrawError, Surface};
+
+use crate::geometry::{CanvasSpace, ModelSpace};
+use crate::graphics::dyn_vertex_buffer::DynVertexBuffer;
+use crate::graphics::primitives::{Color, ColoredLine, ColoredPoint, Line, LinePoint, ModelVertex};
+use crate::graphics::programs::Programs;
+
+/// Primitives with at most this many vertices are moved to canvas space on
+/// the CPU, relative to the origin set with [`Batcher::set_origin`], so they
+/// can share a draw call with primitives from other objects. Bigger ones keep
+/// their model transform and only merge with primitives that use the same
+/// transform.
+pub const CPU_TRANSFORM_LIMIT: usize = 1024;
+
+/// Collects primitives drawn through a [`DrawingContext`] and draws those
+/// added one after another with the same program and state in one call when
+/// flushed. Batches are drawn in the order they were added, so primitives
+/// keep their place in the draw order.
+///
+/// [`DrawingContext`]: crate::canvas::DrawingContext
+pub struct Batcher {
+    context: Rc<Context>,
+    origin: Point2D<f64, CanvasSpace>,
+    line_strips: Batches<LinePoint>,
+    lines: Batches<Line>,
+    colored_lines: Batches<ColoredLine>,
+    colored_points: Batches<ColoredPoint>,
+    /// The batches in draw order, as their kind and index.
+    order: Vec<(Kind, usize)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Kind {
+    LineStrips,
+    Lines,
+    ColoredLines,
+    ColoredPoints,
+}
+
+struct Batches<T: Copy> {
+    kind: Kind,
+    batches: Vec<Batch<T>>,
+    buffer: DynVertexBuffer<T>,
+}
+
+struct Batch<T> {
+    color: Color,
+    width: f32,
+    opacity: f32,
+    /// `None` if the data was moved to canvas space relative to the origin.
+    model_transform: Option<Transform2D<f64, ModelSpace, CanvasSpace>>,
+    data: Vec<T>,
+    /// Offset of `data` in the buffer, once uploaded.
+    start: usize,
+}
+
+impl Batcher {
+    pub fn new(facade: &impl Facade) -> Batcher {
+        Batcher {
+            context: facade.get_context().clone(),
+            origin: Point2D::origin(),
+            line_strips: Batches::new(facade, Kind::LineStrips),
+            lines: Batches::new(facade, Kind::Lines),
+            colored_lines: Batches::new(facade, Kind::ColoredLines),
+            colored_points: Batches::new(facade, Kind::ColoredPoints),
+            order: Vec::new(),
+        }
+    }
+
+    /// Canvas point primitives moved on the CPU are made relative to. It
+    /// must be the one of [`Programs::set_origin`] until the next flush.
+    pub fn set_origin(&mut self, origin: Point2D<f64, CanvasSpace>) {
+        self.origin = origin;
+    }
+
+    /// Adds one line strip. Strips are separated by a NaN vertex, which the
+    /// line strip program skips.
+    pub fn add_line_strip(
+        &mut self,
+        strip: &[LinePoint],
+        color: Color,
+        width: f32,
+        opacity: f32,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+    ) {
+        let batch = self.line_strips.get(
+            &mut self.order,
+            color,
+            width,
+            opacity,
+            model_transform,
+            strip.len(),
+        );
+        if !batch.data.is_empty() {
+            batch.data.push(LinePoint::SEPARATOR);
+        }
+        batch.extend(strip, model_transform, self.origin);
+    }
+
+    pub fn add_lines(
+        &mut self,
+        lines: &[Line],
+        color: Color,
+        width: f32,
+        opacity: f32,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+    ) {
+        self.lines
+            .get(
+                &mut self.order,
+                color,
+                width,
+                opacity,
+                model_transform,
+                lines.len(),
+            )
+            .extend(lines, model_transform, self.origin);
+    }
+
+    pub fn add_colored_lines(
+        &mut self,
+        lines: &[ColoredLine],
+        width: f32,
+        opacity: f32,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+    ) {
+        self.colored_lines
+            .get(
+                &mut self.order,
+                Color::WHITE,
+                width,
+                opacity,
+                model_transform,
+                lines.len(),
+            )
+            .extend(lines, model_transform, self.origin);
+    }
+
+    pub fn add_colored_points(
+        &mut self,
+        points: &[ColoredPoint],
+        width: f32,
+        opacity: f32,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+    ) {
+        self.colored_points
+            .get(
+                &mut self.order,
+                Color::WHITE,
+                width,
+                opacity,
+                model_transform,
+                points.len(),
+            )
+            .extend(points, model_transform, self.origin);
+    }
+
+    /// Draws and removes all collected primitives. They are removed even if
+    /// drawing fails, so they can't end up in the next frame.
+    pub fn flush(
+        &mut self,
+        programs: &Programs,
+        frame: &mut impl Surface,
+    ) -> Result<(), DrawError> {
+        // Every batch brings its own opacity, restore the one of the caller.
+        let opacity = programs.opacity();
+        let result = self.flush_batches(programs, frame);
+        programs.set_opacity(opacity);
+        self.clear();
+        result
+    }
+
+    /// Removes all collected primitives without drawing them.
+    pub fn clear(&mut self) {
+        self.line_strips.batches.clear();
+        self.lines.batches.clear();
+        self.colored_lines.batches.clear();
+        self.colored_points.batches.clear();
+        self.order.clear();
+    }
+
+    fn flush_batches(
+        &mut self,
+        programs: &Programs,
+        frame: &mut impl Surface,
+    ) -> Result<(), DrawError> {
+        let context = &self.context;
+        self.line_strips.upload(context);
+        self.lines.upload(context);
+        self.colored_lines.upload(context);
+        self.colored_points.upload(context);
+
+        for &(kind, i) in &self.order {
+            match kind {
+                Kind::LineStrips => {
+                    let (buffer, b) = self.line_strips.slice(i);
+                    draw_batch(programs, b, |transform| {
+                        programs.draw_line_strips(frame, buffer, b.color, b.width, transform)
+                    })?;
+                }
+                Kind::Lines => {
+                    let (buffer, b) = self.lines.slice(i);
+                    draw_batch(programs, b, |transform| {
+                        programs.draw_lines(frame, buffer, b.color, b.width, transform)
+                    })?;
+                }
+                Kind::ColoredLines => {
+                    let (buffer, b) = self.colored_lines.slice(i);
+                    draw_batch(programs, b, |transform| {
+                        programs.draw_colored_lines(frame, buffer, b.width, transform)
+                    })?;
+                }
+                Kind::ColoredPoints => {
+                    let (buffer, b) = self.colored_points.slice(i);
+                    draw_batch(programs, b, |transform| {
+                        programs.draw_colored_points(frame, buffer, b.width, transform)
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sets the state of `batch` and calls `draw` with its model transform. Data
+/// moved on the CPU is already relative to the origin, so it is drawn with
+/// the origin of `programs` at zero.
+fn draw_batch<T>(
+    programs: &Programs,
+    batch: &Batch<T>,
+    draw: impl FnOnce(Transform2D<f64, ModelSpace, CanvasSpace>) -> Result<(), DrawError>,
+) -> Result<(), DrawError> {
+    programs.set_opacity(batch.opacity);
+    match batch.model_transform {
+        Some(transform) => draw(transform),
+        None => {
+            let origin = programs.origin();
+            programs.set_origin(Point2D::origin());
+            let result = draw(Transform2D::identity());
+            programs.set_origin(origin);
+            result
+        }
+    }
+}
+
+impl<T: Vertex + ModelVertex> Batches<T> {
+    fn new(facade: &impl Facade, kind: Kind) -> Batches<T> {
+        Batches {
+            kind,
+            batches: Vec::new(),
+            buffer: DynVertexBuffer::new(facade).unwrap(),
+        }
+    }
+
+    /// Finds or creates the batch for `len` new vertices with the given state.
+    /// Only the last batch of all kinds can take them, merging with an
+    /// earlier one would draw them below what was added in between.
+    fn get(
+        &mut self,
+        order: &mut Vec<(Kind, usize)>,
+        color: Color,
+        width: f32,
+        opacity: f32,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+        len: usize,
+    ) -> &mut Batch<T> {
+        let model_transform = if len <= CPU_TRANSFORM_LIMIT {
+            None
+        } else {
+            Some(model_transform)
+        };
+
+        let last = self.batches.len().wrapping_sub(1);
+        let mergeable = order.last() == Some(&(self.kind, last)) && {
+            let b = &self.batches[last];
+            b.color == color
+                && b.width == width
+                && b.opacity == opacity
+                && b.model_transform == model_transform
+        };
+        if !mergeable {
+            self.batches.push(Batch {
+                color,
+                width,
+                opacity,
+                model_transform,
+                data: Vec::new(),
+                start: 0,
+            });
+            order.push((self.kind, self.batches.len() - 1));
+        }
+        self.batches.last_mut().unwrap()
+    }
+
+    /// Writes the data of all batches into the buffer.
+    fn upload(&mut self, facade: &impl Facade) {
+        if self.batches.is_empty() {
+            return;
+        }
+
+        let mut start = 0;
+        for batch in &mut self.batches {
+            batch.start = start;
+            start += batch.data.len();
+        }
+        self.buffer.clear();
+        self.buffer
+            .extend_n(
+                facade,
+                start,
+                self.batches.iter().flat_map(|b| b.data.iter().copied()),
+            )
+            .unwrap();
+    }
+
+    /// The uploaded data of batch `i` and the batch.
+    fn slice(&self, i: usize) -> (VertexBufferSlice<'_, T>, &Batch<T>) {
+        let batch = &self.batches[i];
+        let range = batch.start..batch.start + batch.data.len();
+        (self.buffer.slice(range).unwrap(), batch)
+    }
+}
+
+impl<T: ModelVertex> Batch<T> {
+    /// Appends `data`, moving it to canvas space relative to `origin` if this
+    /// batch has none of its own model transform.
+    fn extend(
+        &mut self,
+        data: &[T],
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+        origin: Point2D<f64, CanvasSpace>,
+    ) {
+        if self.model_transform == Some(model_transform) {
+            self.data.extend_from_slice(data);
+        } else {
+            self.data
+                .extend(data.iter().map(|v| v.to_canvas(&model_transform, origin)));
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::mem::size_of;
+use std::ops::Range;
 
 use glium::vertex::{BufferCreationError, Vertex, VertexBufferSlice};
 use glium::{backend::Facade, VertexBuffer};
@@ -109,7 +110,7 @@ where
             .unwrap()
             .map();
         self.size += len;
-        f(&mut *mapping);
+        f(&mut mapping);
         Ok(())
     }
 
@@ -136,12 +137,20 @@ where
         self.size = 0;
     }
 
-    pub fn get(&self) -> VertexBufferSlice<T> {
+    pub fn get(&self) -> VertexBufferSlice<'_, T> {
         self.buffer.slice(..self.size).unwrap()
     }
 
+    /// Returns `None` if `range` is not within the written part of the buffer.
+    pub fn slice(&self, range: Range<usize>) -> Option<VertexBufferSlice<'_, T>> {
+        if range.end > self.size {
+            return None;
+        }
+        self.buffer.slice(range)
+    }
+
     pub fn with_mapping<R>(&mut self, f: impl FnOnce(&mut [T]) -> R) -> R {
         let mut mapping = self.buffer.slice_mut(..self.size).unwrap().map();
-        f(&mut *mapping)
+        f(&mut mapping)
     }
 }
@@ -1,3 +1,5 @@
+pub mod batch;
 pub mod dyn_vertex_buffer;
 pub mod primitives;
 pub mod programs;
+pub mod target;
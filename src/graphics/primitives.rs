@@ -1,8 +1,8 @@
-use euclid::Point2D;
+use euclid::{Point2D, Transform2D};
 use glium::implement_vertex;
 use glium::vertex::{Attribute as GLAttribute, AttributeType as GLAttributeType};
 
-use crate::geometry::Line as GLine;
+use crate::geometry::{CanvasSpace, Line as GLine, ModelSpace, Scalar};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -12,6 +12,14 @@ impl Vf2 {
     pub const fn new(x: f32, y: f32) -> Vf2 {
         Vf2(x, y)
     }
+
+    /// `point - origin`, subtracted in the precision of `F` before rounding
+    /// to `f32`. Large coordinates stay exact near `origin`, see
+    /// [`DrawingContext::model_origin`](crate::canvas::DrawingContext::model_origin).
+    pub fn relative<F: Scalar, U>(point: Point2D<F, U>, origin: Point2D<F, U>) -> Vf2 {
+        let v = point - origin;
+        Vf2::new(v.x.as_f32(), v.y.as_f32())
+    }
 }
 
 unsafe impl GLAttribute for Vf2 {
@@ -21,6 +29,17 @@ unsafe impl GLAttribute for Vf2 {
     }
 }
 
+impl Vf2 {
+    fn transform(
+        self,
+        transform: &Transform2D<f64, ModelSpace, CanvasSpace>,
+        origin: Point2D<f64, CanvasSpace>,
+    ) -> Vf2 {
+        let point = transform.transform_point(Point2D::new(self.0 as f64, self.1 as f64));
+        Vf2::relative(point, origin)
+    }
+}
+
 impl<T> From<Point2D<f32, T>> for Vf2 {
     fn from(p: Point2D<f32, T>) -> Self {
         Vf2::new(p.x, p.y)
@@ -192,6 +211,28 @@ impl Color {
     pub fn with_alpha(self, alpha: f32) -> Color {
         Color(self.0, self.1, self.2, alpha)
     }
+
+    /// Linear interpolation from `self` at `t = 0` to `other` at `t = 1`.
+    pub fn mix(self, other: Color, t: f32) -> Color {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Color(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+            mix(self.3, other.3),
+        )
+    }
+}
+
+/// Vertices that can be moved from model to canvas space on the CPU.
+pub trait ModelVertex: Copy {
+    /// Moves the vertex to canvas space relative to `origin`, in `f64` so
+    /// it stays precise where the canvas coordinates are large.
+    fn to_canvas(
+        &self,
+        transform: &Transform2D<f64, ModelSpace, CanvasSpace>,
+        origin: Point2D<f64, CanvasSpace>,
+    ) -> Self;
 }
 
 #[repr(C)]
@@ -201,12 +242,45 @@ pub struct LinePoint {
 }
 implement_vertex!(LinePoint, vertex);
 
+/// Same layout as [`LinePoint`] but bound as `next_vertex`. This lets the
+/// instanced line strip program read a strip a second time, shifted by one.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NextLinePoint {
+    next_vertex: Vf2,
+}
+implement_vertex!(NextLinePoint, next_vertex);
+
 impl LinePoint {
+    /// Splits a line strip in two, no segment is drawn to or from it.
+    pub const SEPARATOR: LinePoint = LinePoint {
+        vertex: Vf2::new(f32::NAN, f32::NAN),
+    };
+
     pub fn from_point<U>(vec: Point2D<f32, U>) -> LinePoint {
         LinePoint {
             vertex: Vf2::new(vec.x, vec.y),
         }
     }
+
+    /// See [`Vf2::relative`].
+    pub fn from_relative<F: Scalar, U>(point: Point2D<F, U>, origin: Point2D<F, U>) -> LinePoint {
+        LinePoint {
+            vertex: Vf2::relative(point, origin),
+        }
+    }
+}
+
+impl ModelVertex for LinePoint {
+    fn to_canvas(
+        &self,
+        transform: &Transform2D<f64, ModelSpace, CanvasSpace>,
+        origin: Point2D<f64, CanvasSpace>,
+    ) -> LinePoint {
+        LinePoint {
+            vertex: self.vertex.transform(transform, origin),
+        }
+    }
 }
 
 #[repr(C)]
@@ -217,6 +291,19 @@ pub struct ColoredPoint {
 }
 implement_vertex!(ColoredPoint, vertex, color);
 
+impl ModelVertex for ColoredPoint {
+    fn to_canvas(
+        &self,
+        transform: &Transform2D<f64, ModelSpace, CanvasSpace>,
+        origin: Point2D<f64, CanvasSpace>,
+    ) -> ColoredPoint {
+        ColoredPoint {
+            vertex: self.vertex.transform(transform, origin),
+            color: self.color,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Line {
@@ -232,8 +319,32 @@ impl Line {
             end: Vf2::new(end.x, end.y),
         }
     }
-    pub fn from_line<U>(line: GLine<U>) -> Line {
-        Line::from_points(line.start, line.end)
+    pub fn from_line<U, F: Scalar>(line: GLine<U, F>) -> Line {
+        Line {
+            start: Vf2::new(line.start.x.as_f32(), line.start.y.as_f32()),
+            end: Vf2::new(line.end.x.as_f32(), line.end.y.as_f32()),
+        }
+    }
+
+    /// See [`Vf2::relative`].
+    pub fn from_relative<U, F: Scalar>(line: &GLine<U, F>, origin: Point2D<F, U>) -> Line {
+        Line {
+            start: Vf2::relative(line.start, origin),
+            end: Vf2::relative(line.end, origin),
+        }
+    }
+}
+
+impl ModelVertex for Line {
+    fn to_canvas(
+        &self,
+        transform: &Transform2D<f64, ModelSpace, CanvasSpace>,
+        origin: Point2D<f64, CanvasSpace>,
+    ) -> Line {
+        Line {
+            start: self.start.transform(transform, origin),
+            end: self.end.transform(transform, origin),
+        }
     }
 }
 
@@ -246,6 +357,20 @@ pub struct ColoredLine {
 }
 implement_vertex!(ColoredLine, start, end, color);
 
+impl ModelVertex for ColoredLine {
+    fn to_canvas(
+        &self,
+        transform: &Transform2D<f64, ModelSpace, CanvasSpace>,
+        origin: Point2D<f64, CanvasSpace>,
+    ) -> ColoredLine {
+        ColoredLine {
+            start: self.start.transform(transform, origin),
+            end: self.end.transform(transform, origin),
+            color: self.color,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Sprite {
@@ -254,3 +379,31 @@ pub struct Sprite {
     pub texture_index: i32,
 }
 implement_vertex!(Sprite, vertex, size, texture_index);
+
+/// Corner of the unit quad that the instanced programs expand every primitive
+/// into. `x` selects the start (-1) or end (1) of a line, `y` the side.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuadCorner {
+    pub corner: Vf2,
+}
+implement_vertex!(QuadCorner, corner);
+
+impl QuadCorner {
+    /// The corners in the same order the geometry shaders emit them, to be
+    /// drawn as a triangle strip.
+    pub const STRIP: [QuadCorner; 4] = [
+        QuadCorner {
+            corner: Vf2::new(-1., 1.),
+        },
+        QuadCorner {
+            corner: Vf2::new(1., 1.),
+        },
+        QuadCorner {
+            corner: Vf2::new(-1., -1.),
+        },
+        QuadCorner {
+            corner: Vf2::new(1., -1.),
+        },
+    ];
+}
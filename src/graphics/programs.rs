@@ -1,27 +1,233 @@
+use std::cell::Cell;
+use std::mem::offset_of;
+
+use glium::backend::Context;
+use glium::index::{IndexBufferSlice, NoIndices, PrimitiveType};
+use glium::program::BlockLayout;
 use glium::program::ProgramChooserCreationError;
 use glium::texture::Texture2d;
-use glium::vertex::VertexBufferSlice;
+use glium::uniforms::{LayoutMismatchError, UniformBlock, UniformBuffer, Uniforms};
+use glium::vertex::{Vertex, VertexBufferSlice, VertexFormat, VerticesSource};
 use glium::{
     backend::Facade,
     uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
 };
 use glium::{program, uniform};
-use glium::{DrawError, DrawParameters, Program, Surface};
+use glium::{
+    Api, CapabilitiesSource, DrawError, DrawParameters, Program, Surface, Version, VertexBuffer,
+};
 
-use euclid::Transform2D;
+use euclid::{Point2D, Transform2D};
 
 use crate::geometry::{CanvasSpace, ModelSpace, ScreenSpace};
 use crate::graphics::primitives::{Color, ColoredLine, Line, LinePoint};
 
-use super::primitives::{ColoredPoint, Sprite};
+use super::primitives::{ColoredPoint, NextLinePoint, QuadCorner, Sprite};
+
+/// How a program turns one vertex (or one strip segment) into a quad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderPath {
+    /// The primitives are drawn as points and expanded by a geometry shader.
+    GeometryShader,
+    /// The primitives are read per instance and one quad is drawn for each.
+    Instanced,
+}
+
+/// A program that is available in up to two variants, one for every
+/// [`RenderPath`]. Drawing uses the variant of the selected path.
+pub struct ExpandedProgram {
+    geometry: Option<Program>,
+    instanced: Option<Program>,
+    path: Cell<RenderPath>,
+}
+
+impl ExpandedProgram {
+    /// `chosen` is the variant glium's program chooser picked for the context.
+    /// If it has a geometry shader, the instanced variant is compiled as well,
+    /// given the context can draw instanced.
+    fn new(
+        chosen: Program,
+        instancing: bool,
+        instanced: impl FnOnce() -> Result<Program, ProgramChooserCreationError>,
+    ) -> Result<ExpandedProgram, ProgramChooserCreationError> {
+        if chosen.has_geometry_shader() {
+            Ok(ExpandedProgram {
+                geometry: Some(chosen),
+                instanced: if instancing { Some(instanced()?) } else { None },
+                path: Cell::new(RenderPath::GeometryShader),
+            })
+        } else if instancing {
+            Ok(ExpandedProgram {
+                geometry: None,
+                instanced: Some(chosen),
+                path: Cell::new(RenderPath::Instanced),
+            })
+        } else {
+            // The fallback variant is useless if we cannot draw it instanced.
+            Err(ProgramChooserCreationError::NoVersion)
+        }
+    }
+
+    pub fn path(&self) -> RenderPath {
+        self.path.get()
+    }
+
+    pub fn supports(&self, path: RenderPath) -> bool {
+        match path {
+            RenderPath::GeometryShader => self.geometry.is_some(),
+            RenderPath::Instanced => self.instanced.is_some(),
+        }
+    }
+
+    /// Selects the path used for drawing. Returns false and keeps the current
+    /// path if the context does not support `path`.
+    pub fn set_path(&self, path: RenderPath) -> bool {
+        if self.supports(path) {
+            self.path.set(path);
+        }
+        self.path() == path
+    }
+
+    pub fn get(&self) -> &Program {
+        let program = match self.path() {
+            RenderPath::GeometryShader => &self.geometry,
+            RenderPath::Instanced => &self.instanced,
+        };
+        program.as_ref().unwrap()
+    }
+}
+
+/// Uniforms shared by all programs, see `shaders/prelude.glsl`. They are
+/// written once per frame with [`Programs::set_frame_uniforms`], every draw
+/// adds its own uniforms on top.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FrameUniforms {
+    view_matrix: [[f32; 4]; 4],
+    pixel_width: [f32; 2],
+    aspect_ratio: f32,
+    time: f32,
+    dpi: f32,
+    // Rounds the block up to a multiple of a vec4, as std140 does.
+    _padding: [f32; 3],
+}
+
+impl FrameUniforms {
+    /// `dimensions` are the ones of the surface drawn to, `time` is in seconds
+    /// and `dpi` is the scale factor of the window.
+    pub fn new(
+        view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+        (w, h): (u32, u32),
+        time: f32,
+        dpi: f32,
+    ) -> FrameUniforms {
+        let [[a, b, _], [c, d, _], [x, y, _]] = mat3(view_transform);
+        FrameUniforms {
+            view_matrix: [
+                [a, b, 0., 0.],
+                [c, d, 0., 0.],
+                [x, y, 1., 0.],
+                [0., 0., 0., 1.],
+            ],
+            pixel_width: [2. / w as f32, 2. / h as f32],
+            aspect_ratio: w as f32 / h as f32,
+            time,
+            dpi,
+            _padding: [0.; 3],
+        }
+    }
+}
+
+impl Default for FrameUniforms {
+    fn default() -> FrameUniforms {
+        FrameUniforms::new(Transform2D::identity(), (1, 1), 0., 1.)
+    }
+}
+
+impl UniformBlock for FrameUniforms {
+    fn matches(layout: &BlockLayout, base_offset: usize) -> Result<(), LayoutMismatchError> {
+        let members = match layout {
+            BlockLayout::Struct { members } => members,
+            _ => {
+                return Err(LayoutMismatchError::LayoutMismatch {
+                    expected: layout.clone(),
+                    obtained: FrameUniforms::build_layout(base_offset),
+                })
+            }
+        };
+
+        for (name, member) in members {
+            let result = match name.as_str() {
+                "view_matrix" => <[[f32; 4]; 4]>::matches(
+                    member,
+                    base_offset + offset_of!(FrameUniforms, view_matrix),
+                ),
+                "pixel_width" => <[f32; 2]>::matches(
+                    member,
+                    base_offset + offset_of!(FrameUniforms, pixel_width),
+                ),
+                "aspect_ratio" => f32::matches(
+                    member,
+                    base_offset + offset_of!(FrameUniforms, aspect_ratio),
+                ),
+                "time" => f32::matches(member, base_offset + offset_of!(FrameUniforms, time)),
+                "dpi" => f32::matches(member, base_offset + offset_of!(FrameUniforms, dpi)),
+                _ => return Err(LayoutMismatchError::MissingField { name: name.clone() }),
+            };
+            result.map_err(|err| LayoutMismatchError::MemberMismatch {
+                member: name.clone(),
+                err: Box::new(err),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn build_layout(base_offset: usize) -> BlockLayout {
+        BlockLayout::Struct {
+            members: vec![
+                (
+                    "view_matrix".to_owned(),
+                    <[[f32; 4]; 4]>::build_layout(
+                        base_offset + offset_of!(FrameUniforms, view_matrix),
+                    ),
+                ),
+                (
+                    "pixel_width".to_owned(),
+                    <[f32; 2]>::build_layout(base_offset + offset_of!(FrameUniforms, pixel_width)),
+                ),
+                (
+                    "aspect_ratio".to_owned(),
+                    f32::build_layout(base_offset + offset_of!(FrameUniforms, aspect_ratio)),
+                ),
+                (
+                    "time".to_owned(),
+                    f32::build_layout(base_offset + offset_of!(FrameUniforms, time)),
+                ),
+                (
+                    "dpi".to_owned(),
+                    f32::build_layout(base_offset + offset_of!(FrameUniforms, dpi)),
+                ),
+            ],
+        }
+    }
+}
 
 pub struct Programs {
     pub parameters: DrawParameters<'static>,
-    pub line_strip: Program,
-    pub line: Program,
-    pub colored_point: Program,
-    pub colored_line: Program,
-    pub sprites: Program,
+    pub line_strip: ExpandedProgram,
+    pub line: ExpandedProgram,
+    pub colored_point: ExpandedProgram,
+    pub colored_line: ExpandedProgram,
+    pub sprites: ExpandedProgram,
+    pub grid: Program,
+    pub mesh: Program,
+    opacity: Cell<f32>,
+    origin: Cell<Point2D<f64, CanvasSpace>>,
+    frame_uniforms: UniformBuffer<FrameUniforms>,
+    quad: VertexBuffer<QuadCorner>,
+    line_point: VertexFormat,
+    next_line_point: VertexFormat,
 }
 
 macro_rules! include_shaders {
@@ -34,9 +240,30 @@ macro_rules! include_shaders {
                 fragment: concat!("#version 330\n",
                     include_str!("shaders/prelude.glsl"), "\n",
                     include_str!(concat!("shaders/", $name, "/frag.glsl"))),
+            },
+            300 es => {
+                vertex: concat!("#version 300 es\n",
+                    include_str!("shaders/prelude_es.glsl"), "\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/vert.glsl"))),
+                fragment: concat!("#version 300 es\n",
+                    include_str!("shaders/prelude_es.glsl"), "\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/frag.glsl"))),
+            },
+            140 => {
+                vertex: concat!("#version 140\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/vert.glsl"))),
+                fragment: concat!("#version 140\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/frag.glsl"))),
             }
         )
     };
+    // The geometry shader variant expands points into quads. Contexts without
+    // geometry shaders get the `quad.glsl` variant instead, which is drawn as
+    // one instanced quad per primitive (see `Programs::draw_expanded`).
     ($display:expr, $name:expr, "vgf") => {
         program!($display,
             330 => {
@@ -49,6 +276,56 @@ macro_rules! include_shaders {
                 fragment: concat!("#version 330\n",
                     include_str!("shaders/prelude.glsl"), "\n",
                     include_str!(concat!("shaders/", $name, "/frag.glsl"))),
+            },
+            300 es => {
+                vertex: concat!("#version 300 es\n",
+                    include_str!("shaders/prelude_es.glsl"), "\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/quad.glsl"))),
+                fragment: concat!("#version 300 es\n",
+                    include_str!("shaders/prelude_es.glsl"), "\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/frag.glsl"))),
+            },
+            140 => {
+                vertex: concat!("#version 140\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/quad.glsl"))),
+                fragment: concat!("#version 140\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/frag.glsl"))),
+            }
+        )
+    };
+    // Only the instanced variant, for contexts that also have the geometry
+    // shader variant.
+    ($display:expr, $name:expr, "quad") => {
+        program!($display,
+            330 => {
+                vertex: concat!("#version 330\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/quad.glsl"))),
+                fragment: concat!("#version 330\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/frag.glsl"))),
+            },
+            300 es => {
+                vertex: concat!("#version 300 es\n",
+                    include_str!("shaders/prelude_es.glsl"), "\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/quad.glsl"))),
+                fragment: concat!("#version 300 es\n",
+                    include_str!("shaders/prelude_es.glsl"), "\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/frag.glsl"))),
+            },
+            140 => {
+                vertex: concat!("#version 140\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/quad.glsl"))),
+                fragment: concat!("#version 140\n",
+                    include_str!("shaders/prelude.glsl"), "\n",
+                    include_str!(concat!("shaders/", $name, "/frag.glsl"))),
             }
         )
     }
@@ -56,12 +333,35 @@ macro_rules! include_shaders {
 
 impl Programs {
     pub fn new(display: &impl Facade) -> Result<Programs, ProgramChooserCreationError> {
+        let instancing = supports_instancing(display.get_context());
         Ok(Programs {
-            line_strip: include_shaders!(display, "line_strip", "vgf")?,
-            line: include_shaders!(display, "line", "vgf")?,
-            colored_line: include_shaders!(display, "colored_line", "vgf")?,
-            colored_point: include_shaders!(display, "colored_point", "vgf")?,
-            sprites: include_shaders!(display, "sprites", "vgf")?,
+            line_strip: ExpandedProgram::new(
+                include_shaders!(display, "line_strip", "vgf")?,
+                instancing,
+                || include_shaders!(display, "line_strip", "quad"),
+            )?,
+            line: ExpandedProgram::new(
+                include_shaders!(display, "line", "vgf")?,
+                instancing,
+                || include_shaders!(display, "line", "quad"),
+            )?,
+            colored_line: ExpandedProgram::new(
+                include_shaders!(display, "colored_line", "vgf")?,
+                instancing,
+                || include_shaders!(display, "colored_line", "quad"),
+            )?,
+            colored_point: ExpandedProgram::new(
+                include_shaders!(display, "colored_point", "vgf")?,
+                instancing,
+                || include_shaders!(display, "colored_point", "quad"),
+            )?,
+            sprites: ExpandedProgram::new(
+                include_shaders!(display, "sprites", "vgf")?,
+                instancing,
+                || include_shaders!(display, "sprites", "quad"),
+            )?,
+            grid: include_shaders!(display, "grid", "vf")?,
+            mesh: include_shaders!(display, "mesh", "vf")?,
             parameters: DrawParameters {
                 blend: glium::Blend {
                     color: glium::BlendingFunction::Addition {
@@ -77,40 +377,139 @@ impl Programs {
                 backface_culling: glium::BackfaceCullingMode::CullingDisabled,
                 ..Default::default()
             },
+            opacity: Cell::new(1.),
+            origin: Cell::new(Point2D::origin()),
+            frame_uniforms: UniformBuffer::new(display, FrameUniforms::default()).unwrap(),
+            quad: VertexBuffer::new(display, &QuadCorner::STRIP).unwrap(),
+            line_point: LinePoint::build_bindings(),
+            next_line_point: NextLinePoint::build_bindings(),
         })
     }
 
+    /// Selects the render path of all programs at once. Returns false if a
+    /// program does not support `path`, those keep their current path.
+    pub fn set_render_path(&self, path: RenderPath) -> bool {
+        let programs = [
+            &self.line_strip,
+            &self.line,
+            &self.colored_point,
+            &self.colored_line,
+            &self.sprites,
+        ];
+        let mut ok = true;
+        for program in programs.iter() {
+            ok &= program.set_path(path);
+        }
+        ok
+    }
+
+    /// Draws every element of `instances` as a quad. On the geometry shader
+    /// path the elements are drawn as points and expanded there, on the
+    /// instanced path they are read per instance on top of the unit quad.
+    fn draw_expanded<'a>(
+        &self,
+        frame: &mut impl Surface,
+        program: &ExpandedProgram,
+        instances: VertexBufferSlice<'a, impl Copy>,
+        uniforms: &impl Uniforms,
+    ) -> Result<(), DrawError> {
+        let path = program.path();
+        let program = program.get();
+        if path == RenderPath::GeometryShader {
+            frame.draw(
+                instances,
+                NoIndices(PrimitiveType::Points),
+                program,
+                uniforms,
+                &self.parameters,
+            )
+        } else {
+            frame.draw(
+                (&self.quad, per_instance(instances.into())),
+                NoIndices(PrimitiveType::TriangleStrip),
+                program,
+                uniforms,
+                &self.parameters,
+            )
+        }
+    }
+
+    /// Multiplies the alpha of everything drawn until the next call.
+    pub fn set_opacity(&self, opacity: f32) {
+        self.opacity.set(opacity);
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity.get()
+    }
+
+    /// Writes the uniforms shared by all draws until the next call. Their
+    /// view transform is relative to the origin, see [`Programs::set_origin`].
+    pub fn set_frame_uniforms(&self, uniforms: FrameUniforms) {
+        self.frame_uniforms.write(&uniforms);
+    }
+
+    /// Canvas point that is subtracted from all positions in `f64` before
+    /// they reach the shaders. Kept close to the camera, it keeps them small
+    /// enough for `f32` when the canvas coordinates are large.
+    pub fn set_origin(&self, origin: Point2D<f64, CanvasSpace>) {
+        self.origin.set(origin);
+    }
+
+    pub fn origin(&self) -> Point2D<f64, CanvasSpace> {
+        self.origin.get()
+    }
+
+    fn model_matrix(
+        &self,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+    ) -> [[f32; 3]; 3] {
+        model_matrix(model_transform, self.origin.get())
+    }
+
     pub fn draw_line_strips(
         &self,
         frame: &mut impl Surface,
         vertex_buffer: VertexBufferSlice<LinePoint>,
         color: Color,
         width: f32,
-        model_transform: Transform2D<f32, ModelSpace, CanvasSpace>,
-        view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
     ) -> Result<(), DrawError> {
-        let (w, h) = frame.get_dimensions();
-        let aspect_ratio = w as f32 / h as f32;
-        let mt: [[f32; 2]; 3] = model_transform.to_arrays();
-        let vt: [[f32; 2]; 3] = view_transform.to_arrays();
+        let uniforms = uniform! {
+            FrameUniforms: &self.frame_uniforms,
+            opacity: self.opacity.get(),
+            model_transform: self.model_matrix(model_transform),
+            width: width,
+            color: color.to_rgb_array(),
+        };
+
+        if self.line_strip.path() == RenderPath::GeometryShader {
+            return frame.draw(
+                vertex_buffer,
+                NoIndices(PrimitiveType::LineStrip),
+                self.line_strip.get(),
+                &uniforms,
+                &self.parameters,
+            );
+        }
+
+        // Every segment of the strip is one instance, reading its start from
+        // the strip and its end from the strip shifted by one vertex.
+        let len = vertex_buffer.len();
+        if len < 2 {
+            return Ok(());
+        }
+        let starts = vertex_buffer.slice(..len - 1).unwrap().as_slice_any();
+        let ends = vertex_buffer.slice(1..).unwrap().as_slice_any();
         frame.draw(
-            vertex_buffer,
-            glium::index::NoIndices(glium::index::PrimitiveType::LineStrip),
-            &self.line_strip,
-            &uniform! {
-                width: width,
-                pixel_width: [2. / w as f32, 2. / h as f32],
-                color: color.to_rgb_array(),
-                model_transform: [
-                    [mt[0][0], mt[0][1], 0.],
-                    [mt[1][0], mt[1][1], 0.],
-                    [mt[2][0], mt[2][1], 1.]],
-                view_transform: [
-                    [vt[0][0], vt[0][1], 0.],
-                    [vt[1][0], vt[1][1], 0.],
-                    [vt[2][0], vt[2][1], 1.]],
-                aspect_ratio: aspect_ratio,
-            },
+            (
+                &self.quad,
+                VerticesSource::VertexBuffer(starts, &self.line_point, true),
+                VerticesSource::VertexBuffer(ends, &self.next_line_point, true),
+            ),
+            NoIndices(PrimitiveType::TriangleStrip),
+            self.line_strip.get(),
+            &uniforms,
             &self.parameters,
         )
     }
@@ -121,32 +520,19 @@ impl Programs {
         vertex_buffer: VertexBufferSlice<Line>,
         color: Color,
         width: f32,
-        model_transform: Transform2D<f32, ModelSpace, CanvasSpace>,
-        view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
     ) -> Result<(), DrawError> {
-        let (w, h) = frame.get_dimensions();
-        let aspect_ratio = w as f32 / h as f32;
-        let mt: [[f32; 2]; 3] = model_transform.to_arrays();
-        let vt: [[f32; 2]; 3] = view_transform.to_arrays();
-        frame.draw(
-            vertex_buffer,
-            glium::index::NoIndices(glium::index::PrimitiveType::Points),
+        self.draw_expanded(
+            frame,
             &self.line,
+            vertex_buffer,
             &uniform! {
+                FrameUniforms: &self.frame_uniforms,
+                opacity: self.opacity.get(),
+                model_transform: self.model_matrix(model_transform),
                 width: width,
-                pixel_width: [2. / w as f32, 2. / h as f32],
                 color: color.to_rgb_array(),
-                model_transform: [
-                    [mt[0][0], mt[0][1], 0.],
-                    [mt[1][0], mt[1][1], 0.],
-                    [mt[2][0], mt[2][1], 1.]],
-                view_transform: [
-                    [vt[0][0], vt[0][1], 0.],
-                    [vt[1][0], vt[1][1], 0.],
-                    [vt[2][0], vt[2][1], 1.]],
-                aspect_ratio: aspect_ratio,
             },
-            &self.parameters,
         )
     }
 
@@ -155,31 +541,18 @@ impl Programs {
         frame: &mut impl Surface,
         vertex_buffer: VertexBufferSlice<ColoredLine>,
         width: f32,
-        model_transform: Transform2D<f32, ModelSpace, CanvasSpace>,
-        view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
     ) -> Result<(), DrawError> {
-        let (w, h) = frame.get_dimensions();
-        let aspect_ratio = w as f32 / h as f32;
-        let mt: [[f32; 2]; 3] = model_transform.to_arrays();
-        let vt: [[f32; 2]; 3] = view_transform.to_arrays();
-        frame.draw(
-            vertex_buffer,
-            glium::index::NoIndices(glium::index::PrimitiveType::Points),
+        self.draw_expanded(
+            frame,
             &self.colored_line,
+            vertex_buffer,
             &uniform! {
+                FrameUniforms: &self.frame_uniforms,
+                opacity: self.opacity.get(),
+                model_transform: self.model_matrix(model_transform),
                 width: width,
-                pixel_width: [2. / w as f32, 2. / h as f32],
-                model_transform: [
-                    [mt[0][0], mt[0][1], 0.],
-                    [mt[1][0], mt[1][1], 0.],
-                    [mt[2][0], mt[2][1], 1.]],
-                view_transform: [
-                    [vt[0][0], vt[0][1], 0.],
-                    [vt[1][0], vt[1][1], 0.],
-                    [vt[2][0], vt[2][1], 1.]],
-                aspect_ratio: aspect_ratio,
             },
-            &self.parameters,
         )
     }
 
@@ -188,31 +561,18 @@ impl Programs {
         frame: &mut impl Surface,
         vertex_buffer: VertexBufferSlice<ColoredPoint>,
         width: f32,
-        model_transform: Transform2D<f32, ModelSpace, CanvasSpace>,
-        view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
     ) -> Result<(), DrawError> {
-        let (w, h) = frame.get_dimensions();
-        let aspect_ratio = w as f32 / h as f32;
-        let mt: [[f32; 2]; 3] = model_transform.to_arrays();
-        let vt: [[f32; 2]; 3] = view_transform.to_arrays();
-        frame.draw(
-            vertex_buffer,
-            glium::index::NoIndices(glium::index::PrimitiveType::Points),
+        self.draw_expanded(
+            frame,
             &self.colored_point,
+            vertex_buffer,
             &uniform! {
+                FrameUniforms: &self.frame_uniforms,
+                opacity: self.opacity.get(),
+                model_transform: self.model_matrix(model_transform),
                 width: width,
-                pixel_width: [2. / w as f32, 2. / h as f32],
-                model_transform: [
-                    [mt[0][0], mt[0][1], 0.],
-                    [mt[1][0], mt[1][1], 0.],
-                    [mt[2][0], mt[2][1], 1.]],
-                view_transform: [
-                    [vt[0][0], vt[0][1], 0.],
-                    [vt[1][0], vt[1][1], 0.],
-                    [vt[2][0], vt[2][1], 1.]],
-                aspect_ratio: aspect_ratio,
             },
-            &self.parameters,
         )
     }
 
@@ -221,27 +581,154 @@ impl Programs {
         frame: &mut impl Surface,
         vertex_buffer: VertexBufferSlice<Sprite>,
         texture: &Texture2d,
-        model_transform: Transform2D<f32, ModelSpace, CanvasSpace>,
-        view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
     ) -> Result<(), DrawError> {
-        let mt: [[f32; 2]; 3] = model_transform.to_arrays();
-        let vt: [[f32; 2]; 3] = view_transform.to_arrays();
-        frame.draw(
-            vertex_buffer,
-            glium::index::NoIndices(glium::index::PrimitiveType::Points),
+        self.draw_expanded(
+            frame,
             &self.sprites,
+            vertex_buffer,
             &uniform! {
+                FrameUniforms: &self.frame_uniforms,
+                opacity: self.opacity.get(),
+                model_transform: self.model_matrix(model_transform),
                 sprite_texture: texture.sampled().magnify_filter(MagnifySamplerFilter::Nearest).minify_filter(MinifySamplerFilter::NearestMipmapNearest),
-                model_transform: [
-                    [mt[0][0], mt[0][1], 0.],
-                    [mt[1][0], mt[1][1], 0.],
-                    [mt[2][0], mt[2][1], 1.]],
-                view_transform: [
-                    [vt[0][0], vt[0][1], 0.],
-                    [vt[1][0], vt[1][1], 0.],
-                    [vt[2][0], vt[2][1], 1.]],
+            },
+        )
+    }
+
+    /// Draws the triangles of `indices`, the colors are interpolated between
+    /// their vertices.
+    pub fn draw_mesh(
+        &self,
+        frame: &mut impl Surface,
+        vertex_buffer: VertexBufferSlice<ColoredPoint>,
+        indices: IndexBufferSlice<u32>,
+        model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+    ) -> Result<(), DrawError> {
+        frame.draw(
+            vertex_buffer,
+            indices,
+            &self.mesh,
+            &uniform! {
+                FrameUniforms: &self.frame_uniforms,
+                opacity: self.opacity.get(),
+                model_transform: self.model_matrix(model_transform),
+            },
+            &self.parameters,
+        )
+    }
+
+    /// Covers the whole surface with the grid lines at multiples of
+    /// `minor_spacing` and `major_spacing` and the axes.
+    pub fn draw_grid(
+        &self,
+        frame: &mut impl Surface,
+        (minor_spacing, major_spacing): (f32, f32),
+        minor_alpha: f32,
+        minor_color: Color,
+        major_color: Color,
+        axis_color: Color,
+    ) -> Result<(), DrawError> {
+        // The lines repeat every major step, so the shader only needs the
+        // origin modulo that. The axes are far away when that is imprecise.
+        let origin = self.origin.get();
+        let major = major_spacing as f64;
+        let grid_origin = [
+            origin.x.rem_euclid(major) as f32,
+            origin.y.rem_euclid(major) as f32,
+        ];
+        frame.draw(
+            &self.quad,
+            NoIndices(PrimitiveType::TriangleStrip),
+            &self.grid,
+            &uniform! {
+                FrameUniforms: &self.frame_uniforms,
+                opacity: self.opacity.get(),
+                minor_spacing: minor_spacing,
+                major_spacing: major_spacing,
+                minor_alpha: minor_alpha,
+                minor_color: minor_color.to_rgba_array(),
+                major_color: major_color.to_rgba_array(),
+                axis_color: axis_color.to_rgba_array(),
+                grid_origin: grid_origin,
+                axis_origin: [origin.x as f32, origin.y as f32],
             },
             &self.parameters,
         )
     }
 }
+
+/// Expands a 2D affine transform into the `mat3` the shaders expect.
+/// `model_transform` as a `mat3`, followed by the move to `origin`. The
+/// translation is subtracted in `f64` before it is rounded to `f32`.
+fn model_matrix(
+    model_transform: Transform2D<f64, ModelSpace, CanvasSpace>,
+    origin: Point2D<f64, CanvasSpace>,
+) -> [[f32; 3]; 3] {
+    let mut matrix = mat3(model_transform.cast());
+    matrix[2][0] = (model_transform.m31 - origin.x) as f32;
+    matrix[2][1] = (model_transform.m32 - origin.y) as f32;
+    matrix
+}
+
+fn mat3<Src, Dst>(transform: Transform2D<f32, Src, Dst>) -> [[f32; 3]; 3] {
+    let t: [[f32; 2]; 3] = transform.to_arrays();
+    [
+        [t[0][0], t[0][1], 0.],
+        [t[1][0], t[1][1], 0.],
+        [t[2][0], t[2][1], 1.],
+    ]
+}
+
+fn per_instance(source: VerticesSource) -> VerticesSource {
+    match source {
+        VerticesSource::VertexBuffer(buffer, format, _) => {
+            VerticesSource::VertexBuffer(buffer, format, true)
+        }
+        VerticesSource::Marker { len, .. } => VerticesSource::Marker {
+            len,
+            per_instance: true,
+        },
+    }
+}
+
+fn supports_instancing(context: &Context) -> bool {
+    match *context.get_opengl_version() {
+        Version(Api::Gl, major, minor) => {
+            (major, minor) >= (3, 3) || context.get_extensions().gl_arb_instanced_arrays
+        }
+        Version(Api::GlEs, major, _) => major >= 3,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use euclid::{vec2, Angle};
+
+    #[test]
+    fn frame_uniforms_use_std140_offsets() {
+        assert_eq!(offset_of!(FrameUniforms, view_matrix), 0);
+        assert_eq!(offset_of!(FrameUniforms, pixel_width), 64);
+        assert_eq!(offset_of!(FrameUniforms, aspect_ratio), 72);
+        assert_eq!(offset_of!(FrameUniforms, time), 76);
+        assert_eq!(offset_of!(FrameUniforms, dpi), 80);
+        assert_eq!(std::mem::size_of::<FrameUniforms>(), 96);
+    }
+
+    #[test]
+    fn it_moves_models_to_the_origin() {
+        // At 3e7 `f32` only has steps of 2, the offset of 1/8 is kept.
+        let transform = Transform2D::rotation(Angle::frac_pi_2()).then_translate(vec2(3e7, -3e7));
+        let origin = Point2D::new(3e7 - 0.125, -3e7 + 0.125);
+        let matrix = model_matrix(transform, origin);
+        assert_eq!(matrix[2], [0.125, -0.125, 1.]);
+        assert_eq!(&matrix[..2], &mat3(transform.cast::<f32>())[..2]);
+
+        // A model placed between the steps of `f32` keeps its place as well.
+        let transform = Transform2D::translation(3e7 + 0.375, -3e7 - 0.625);
+        let matrix = model_matrix(transform, Point2D::new(3e7, -3e7));
+        assert_eq!(matrix[2], [0.375, -0.625, 1.]);
+    }
+}
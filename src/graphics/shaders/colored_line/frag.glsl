@@ -5,14 +5,14 @@ in float g_len;
 
 out vec4 f_color;
 
-uniform vec2 pixel_width;
 uniform float width;
+uniform float opacity;
 
 float line_alpha();
 
 void main() {
     float alpha = line_alpha();
-    f_color = vec4(g_color.rgb, g_color.a * alpha);
+    f_color = vec4(g_color.rgb, g_color.a * alpha * opacity);
 }
 
 float line_alpha() {
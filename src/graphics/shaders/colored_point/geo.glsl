@@ -7,13 +7,11 @@ in vec4[1] v_color;
 out vec2 g_uv;
 out vec4 g_color;
 
-uniform vec2 pixel_width;
-uniform float aspect_ratio;
 uniform float width;
 uniform mat3 model_transform;
-uniform mat3 view_transform;
 
 void main() {
+    mat3 view_transform = mat3(view_matrix);
 
     float half_width = width / 2;
     vec3 vertex = model_transform * vec3(v_vertex[0], 1);
@@ -0,0 +1,29 @@
+in vec2 corner;
+in vec2 vertex;
+in vec4 color;
+
+out vec2 g_uv;
+out vec4 g_color;
+
+uniform float width;
+uniform mat3 model_transform;
+
+void main() {
+    mat3 view_transform = mat3(view_matrix);
+
+    float half_width = width / 2.0;
+    vec3 p_vertex = view_transform * model_transform * vec3(vertex, 1.0);
+
+    vec3 p_tangent = view_transform * vec3(1.0, 0.0, 0.0);
+    vec3 p_normal = view_transform * vec3(0.0, 1.0, 0.0);
+
+    vec3 pn_tangent = normalize(p_tangent);
+    vec3 pn_normal = normalize(p_normal);
+
+    vec3 p = p_vertex + (corner.y * p_normal + corner.x * p_tangent) * half_width
+        + (corner.y * pn_normal + corner.x * pn_tangent) * vec3(pixel_width, 1.0);
+
+    g_color = color;
+    g_uv = vec2(corner.x, -corner.y);
+    gl_Position = vec4(p.xy, 0.0, 1.0);
+}
@@ -0,0 +1,34 @@
+in vec2 v_pos;
+
+out vec4 f_color;
+
+uniform float minor_spacing;
+uniform float major_spacing;
+uniform float minor_alpha;
+uniform vec4 minor_color;
+uniform vec4 major_color;
+uniform vec4 axis_color;
+uniform float opacity;
+uniform vec2 grid_origin;
+uniform vec2 axis_origin;
+
+float line_alpha(vec2 dist);
+
+void main() {
+    vec2 pos = v_pos + grid_origin;
+    vec2 minor = abs(fract(pos / minor_spacing + 0.5) - 0.5) * minor_spacing;
+    vec2 major = abs(fract(pos / major_spacing + 0.5) - 0.5) * major_spacing;
+
+    vec4 color = vec4(minor_color.rgb, minor_color.a * minor_alpha * line_alpha(minor));
+    color = mix(color, major_color, line_alpha(major));
+    color = mix(color, axis_color, line_alpha(abs(v_pos + axis_origin)));
+    f_color = vec4(color.rgb, color.a * opacity);
+}
+
+// Coverage of a one pixel wide line, `dist` is the distance to the closest
+// vertical and horizontal line in canvas space. `v_pos` is relative to the
+// origin of the frame, see `Programs::set_origin`.
+float line_alpha(vec2 dist) {
+    vec2 pixels = dist / fwidth(v_pos);
+    return 1.0 - clamp(min(pixels.x, pixels.y) - 0.5, 0.0, 1.0);
+}
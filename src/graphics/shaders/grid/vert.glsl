@@ -0,0 +1,9 @@
+in vec2 corner;
+
+out vec2 v_pos;
+
+void main() {
+    mat3 screen_to_canvas = inverse(mat3(view_matrix));
+    v_pos = (screen_to_canvas * vec3(corner, 1.0)).xy;
+    gl_Position = vec4(corner, 0.0, 1.0);
+}
@@ -8,13 +8,11 @@ out vec2 g_uv;
 out vec2 g_uv_pw;
 out float g_len;
 
-uniform vec2 pixel_width;
 uniform float width;
-uniform float aspect_ratio;
 uniform mat3 model_transform;
-uniform mat3 view_transform;
 
 void main() {
+    mat3 view_transform = mat3(view_matrix);
 
     float half_width = width / 2;
     vec3 start = model_transform * vec3(v_start[0], 1);
@@ -0,0 +1,44 @@
+in vec2 corner;
+in vec2 start;
+in vec2 end;
+
+out vec2 g_uv;
+out vec2 g_uv_pw;
+out float g_len;
+
+uniform float width;
+uniform mat3 model_transform;
+
+void main() {
+    mat3 view_transform = mat3(view_matrix);
+
+    float half_width = width / 2.0;
+    vec3 p_start = model_transform * vec3(start, 1.0);
+    vec3 p_end = model_transform * vec3(end, 1.0);
+    float len = length(p_end - p_start);
+    vec3 tangent = (p_end - p_start) / len;
+    vec3 normal = tangent.yxz * vec3(-1.0, 1.0, 1.0);
+
+    vec3 p_tangent = view_transform * tangent;
+    vec3 p_normal = view_transform * normal;
+    p_start = view_transform * p_start;
+    p_end = view_transform * p_end;
+
+    vec3 pn_tangent = normalize(p_tangent);
+    vec3 pn_normal = normalize(p_normal);
+
+    vec3 base = corner.x < 0.0 ? p_start : p_end;
+    vec3 p = base + (corner.y * p_normal + corner.x * p_tangent) * half_width
+        + (corner.y * pn_normal + corner.x * pn_tangent) * vec3(pixel_width, 1.0);
+
+    float uv_pw_height = length(pn_tangent.xy * pixel_width) / length(p_tangent * half_width);
+    float uv_pw_width = length(pn_normal.xy * pixel_width) / length(p_normal * half_width);
+    float uv_height = 1.0 + uv_pw_height;
+    float uv_width = 1.0 + uv_pw_width;
+    float uv_len = len / half_width;
+
+    g_uv_pw = vec2(uv_pw_height, uv_pw_width);
+    g_uv = vec2(corner.x < 0.0 ? -uv_height : uv_len + uv_height, -corner.y * uv_width);
+    g_len = uv_len;
+    gl_Position = vec4(p.xy, 0.0, 1.0);
+}
@@ -7,14 +7,12 @@ out vec2 g_uv;
 out vec2 g_uv_pw;
 out float g_len;
 
-uniform vec2 pixel_width;
 uniform float width;
-uniform float aspect_ratio;
 uniform mat3 model_transform;
-uniform mat3 view_transform;
 
 
 void main() {
+    mat3 view_transform = mat3(view_matrix);
     if (isnan(v_vertex[0].x) || isnan(v_vertex[0].y) || isnan(v_vertex[1].x) || isnan(v_vertex[1].y)) {
         return;
     }
@@ -0,0 +1,9 @@
+in vec4 v_color;
+
+out vec4 f_color;
+
+uniform float opacity;
+
+void main() {
+    f_color = vec4(v_color.rgb, v_color.a * opacity);
+}
@@ -0,0 +1,12 @@
+in vec2 vertex;
+in vec4 color;
+
+out vec4 v_color;
+
+uniform mat3 model_transform;
+
+void main() {
+    vec3 p = mat3(view_matrix) * model_transform * vec3(vertex, 1.0);
+    v_color = color;
+    gl_Position = vec4(p.xy, 0.0, 1.0);
+}
@@ -1 +1,10 @@
-#define M_PI 3.1415926535897932384626433832795
\ No newline at end of file
+#define M_PI 3.1415926535897932384626433832795
+
+// Shared by all programs, written once per `Canvas::draw`.
+layout(std140) uniform FrameUniforms {
+    mat4 view_matrix;
+    vec2 pixel_width;
+    float aspect_ratio;
+    float time;
+    float dpi;
+};
@@ -3,7 +3,9 @@ in vec2 g_uv;
 out vec4 f_color;
 
 uniform sampler2D sprite_texture;
+uniform float opacity;
 
 void main() {
     f_color = texture(sprite_texture, g_uv);
+    f_color.a *= opacity;
 }
\ No newline at end of file
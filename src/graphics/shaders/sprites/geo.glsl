@@ -8,9 +8,9 @@ in int[1] v_texture_index;
 out vec2 g_uv;
 
 uniform mat3 model_transform;
-uniform mat3 view_transform;
 
 void main() {
+    mat3 view_transform = mat3(view_matrix);
 
     mat3 mv = view_transform * model_transform;
 
@@ -0,0 +1,23 @@
+in vec2 corner;
+in vec2 vertex;
+in vec2 size;
+in int texture_index;
+
+out vec2 g_uv;
+
+uniform mat3 model_transform;
+
+void main() {
+    mat3 view_transform = mat3(view_matrix);
+
+    mat3 mv = view_transform * model_transform;
+
+    vec3 p = mv * vec3(vertex + size / 2.0 * corner, 1.0);
+
+    float uv_size = 1.0 / 8.0;
+
+    vec2 uv_origin = vec2(texture_index % 8, 7 - texture_index / 8) / 8.0;
+
+    g_uv = uv_origin + (corner * 0.5 + 0.5) * uv_size;
+    gl_Position = vec4(p.xy, 0.0, 1.0);
+}
@@ -0,0 +1,227 @@
+use std::rc::Rc;
+
+use glium::backend::{Context, Facade};
+use glium::framebuffer::{MultiOutputFrameBuffer, SimpleFrameBuffer};
+use glium::index::IndicesSource;
+use glium::texture::Texture2d;
+use glium::uniforms::{MagnifySamplerFilter, Uniforms};
+use glium::vertex::MultiVerticesSource;
+use glium::{BlitTarget, DrawError, DrawParameters, Frame, Program, Rect, Surface};
+
+/// Surface the canvas draws to, either the window or a texture, optionally
+/// restricted to a viewport.
+///
+/// Textures are wrapped in a framebuffer for every call. glium caches the
+/// framebuffer objects, so this is cheap and keeps the target free of the
+/// framebuffer's lifetime.
+pub struct RenderTarget<'a> {
+    surface: TargetSurface<'a>,
+    viewport: Option<Rect>,
+}
+
+enum TargetSurface<'a> {
+    Frame(&'a mut Frame),
+    Texture(&'a Texture2d, Rc<Context>),
+}
+
+impl<'a> RenderTarget<'a> {
+    pub fn texture(facade: &impl Facade, texture: &'a Texture2d) -> RenderTarget<'a> {
+        RenderTarget {
+            surface: TargetSurface::Texture(texture, facade.get_context().clone()),
+            viewport: None,
+        }
+    }
+
+    /// Restricts drawing and clearing to `viewport`, which also becomes the
+    /// dimensions of the target. Blitting still uses the whole surface.
+    pub fn with_viewport(self, viewport: Rect) -> RenderTarget<'a> {
+        RenderTarget {
+            viewport: Some(viewport),
+            ..self
+        }
+    }
+
+    /// Whether this draws to the window rather than a texture.
+    pub fn is_frame(&self) -> bool {
+        matches!(self.surface, TargetSurface::Frame(_))
+    }
+
+    pub fn reborrow(&mut self) -> RenderTarget<'_> {
+        let surface = match &mut self.surface {
+            TargetSurface::Frame(frame) => TargetSurface::Frame(frame),
+            TargetSurface::Texture(texture, context) => {
+                TargetSurface::Texture(texture, context.clone())
+            }
+        };
+        RenderTarget {
+            surface,
+            viewport: self.viewport,
+        }
+    }
+}
+
+impl<'a> From<&'a mut Frame> for RenderTarget<'a> {
+    fn from(frame: &'a mut Frame) -> Self {
+        RenderTarget {
+            surface: TargetSurface::Frame(frame),
+            viewport: None,
+        }
+    }
+}
+
+fn framebuffer<'a>(texture: &'a Texture2d, context: &Rc<Context>) -> SimpleFrameBuffer<'a> {
+    SimpleFrameBuffer::new(context, texture).unwrap()
+}
+
+impl<'t> Surface for RenderTarget<'t> {
+    fn clear(
+        &mut self,
+        rect: Option<&Rect>,
+        color: Option<(f32, f32, f32, f32)>,
+        color_srgb: bool,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        let rect = match (self.viewport, rect) {
+            (Some(viewport), Some(rect)) => Some(Rect {
+                left: viewport.left + rect.left,
+                bottom: viewport.bottom + rect.bottom,
+                ..*rect
+            }),
+            (viewport, rect) => viewport.or_else(|| rect.copied()),
+        };
+        let rect = rect.as_ref();
+        match &mut self.surface {
+            TargetSurface::Frame(frame) => frame.clear(rect, color, color_srgb, depth, stencil),
+            TargetSurface::Texture(texture, context) => {
+                framebuffer(texture, context).clear(rect, color, color_srgb, depth, stencil)
+            }
+        }
+    }
+
+    fn get_dimensions(&self) -> (u32, u32) {
+        if let Some(viewport) = self.viewport {
+            return (viewport.width, viewport.height);
+        }
+        match &self.surface {
+            TargetSurface::Frame(frame) => frame.get_dimensions(),
+            TargetSurface::Texture(texture, _) => texture.dimensions(),
+        }
+    }
+
+    fn get_depth_buffer_bits(&self) -> Option<u16> {
+        match &self.surface {
+            TargetSurface::Frame(frame) => frame.get_depth_buffer_bits(),
+            TargetSurface::Texture(..) => None,
+        }
+    }
+
+    fn get_stencil_buffer_bits(&self) -> Option<u16> {
+        match &self.surface {
+            TargetSurface::Frame(frame) => frame.get_stencil_buffer_bits(),
+            TargetSurface::Texture(..) => None,
+        }
+    }
+
+    fn draw<'a, 'b, V, I, U>(
+        &mut self,
+        vertices: V,
+        indices: I,
+        program: &Program,
+        uniforms: &U,
+        draw_parameters: &DrawParameters,
+    ) -> Result<(), DrawError>
+    where
+        V: MultiVerticesSource<'b>,
+        I: Into<IndicesSource<'a>>,
+        U: Uniforms,
+    {
+        let mut parameters;
+        let draw_parameters = match self.viewport {
+            Some(viewport) => {
+                parameters = draw_parameters.clone();
+                parameters.viewport = Some(viewport);
+                parameters.scissor = Some(viewport);
+                &parameters
+            }
+            None => draw_parameters,
+        };
+        match &mut self.surface {
+            TargetSurface::Frame(frame) => {
+                frame.draw(vertices, indices, program, uniforms, draw_parameters)
+            }
+            TargetSurface::Texture(texture, context) => framebuffer(texture, context).draw(
+                vertices,
+                indices,
+                program,
+                uniforms,
+                draw_parameters,
+            ),
+        }
+    }
+
+    fn blit_from_frame(
+        &self,
+        source_rect: &Rect,
+        target_rect: &BlitTarget,
+        filter: MagnifySamplerFilter,
+    ) {
+        match &self.surface {
+            TargetSurface::Frame(frame) => frame.blit_from_frame(source_rect, target_rect, filter),
+            TargetSurface::Texture(texture, context) => {
+                framebuffer(texture, context).blit_from_frame(source_rect, target_rect, filter)
+            }
+        }
+    }
+
+    fn blit_from_simple_framebuffer(
+        &self,
+        source: &SimpleFrameBuffer,
+        source_rect: &Rect,
+        target_rect: &BlitTarget,
+        filter: MagnifySamplerFilter,
+    ) {
+        match &self.surface {
+            TargetSurface::Frame(frame) => {
+                frame.blit_from_simple_framebuffer(source, source_rect, target_rect, filter)
+            }
+            TargetSurface::Texture(texture, context) => framebuffer(texture, context)
+                .blit_from_simple_framebuffer(source, source_rect, target_rect, filter),
+        }
+    }
+
+    fn blit_from_multioutput_framebuffer(
+        &self,
+        source: &MultiOutputFrameBuffer,
+        source_rect: &Rect,
+        target_rect: &BlitTarget,
+        filter: MagnifySamplerFilter,
+    ) {
+        match &self.surface {
+            TargetSurface::Frame(frame) => {
+                frame.blit_from_multioutput_framebuffer(source, source_rect, target_rect, filter)
+            }
+            TargetSurface::Texture(texture, context) => framebuffer(texture, context)
+                .blit_from_multioutput_framebuffer(source, source_rect, target_rect, filter),
+        }
+    }
+
+    fn blit_color<S>(
+        &self,
+        source_rect: &Rect,
+        target: &S,
+        target_rect: &BlitTarget,
+        filter: MagnifySamplerFilter,
+    ) where
+        S: Surface,
+    {
+        match &self.surface {
+            TargetSurface::Frame(frame) => {
+                frame.blit_color(source_rect, target, target_rect, filter)
+            }
+            TargetSurface::Texture(texture, context) => {
+                framebuffer(texture, context).blit_color(source_rect, target, target_rect, filter)
+            }
+        }
+    }
+}
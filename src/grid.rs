@@ -0,0 +1,274 @@
+use euclid::{Point2D, Transform2D};
+use imgui::{im_str, Condition, StyleVar, Ui, Window};
+
+use crate::canvas::{Canvas, CanvasError, CanvasObject, DrawingContext};
+use crate::geometry::{CanvasSpace, ScreenSpace};
+use crate::graphics::primitives::Color;
+
+/// Labels drawn along each axis at most, the spacing keeps them far fewer.
+const MAX_LABELS: usize = 1000;
+
+/// How the grid spacing changes with the zoom.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GridSteps {
+    /// Minor lines at powers of ten, every tenth line is a major line.
+    PowersOfTen,
+    /// Minor lines at powers of two, every fourth line is a major line.
+    PowersOfTwo,
+}
+
+impl GridSteps {
+    fn base(self) -> f32 {
+        match self {
+            GridSteps::PowersOfTen => 10.,
+            GridSteps::PowersOfTwo => 2.,
+        }
+    }
+
+    fn subdivisions(self) -> f32 {
+        match self {
+            GridSteps::PowersOfTen => 10.,
+            GridSteps::PowersOfTwo => 4.,
+        }
+    }
+
+    /// Number of decimals needed to print multiples of `spacing` exactly.
+    pub(crate) fn decimals(self, spacing: f32) -> usize {
+        if spacing >= 1. {
+            0
+        } else {
+            (-spacing.log(self.base())).round() as usize
+        }
+    }
+
+    /// Smallest spacing whose minor steps are at least `min_spacing` pixels
+    /// apart.
+    pub(crate) fn spacing(self, min_spacing: f32, pixels_per_unit: f32) -> GridSpacing {
+        let base = self.base();
+        let min = min_spacing / pixels_per_unit;
+        let minor = base.powf(min.log(base).ceil());
+        let faded = (minor * pixels_per_unit - min_spacing) / (min_spacing * (base - 1.));
+        GridSpacing {
+            minor,
+            major: minor * self.subdivisions(),
+            minor_alpha: faded.clamp(0., 1.),
+        }
+    }
+}
+
+/// Background grid with major and minor lines and the axes. It is drawn in a
+/// single pass by a fragment shader, so nothing has to be generated per frame.
+///
+/// Draw it first, everything else is drawn on top of it.
+#[derive(Clone, Debug)]
+pub struct Grid {
+    pub steps: GridSteps,
+    /// Minor lines closer than this many pixels are hidden, they fade in until
+    /// they are one step further apart.
+    pub min_spacing: f32,
+    pub minor_color: Color,
+    pub major_color: Color,
+    pub axis_color: Color,
+    /// Draw the coordinates of the major lines in [`Grid::draw_labels`].
+    pub labels: bool,
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid {
+            steps: GridSteps::PowersOfTen,
+            min_spacing: 8.,
+            minor_color: Color::GAINSBORO,
+            major_color: Color::SILVER,
+            axis_color: Color::DIM_GRAY,
+            labels: true,
+        }
+    }
+}
+
+/// Spacing of the grid lines in canvas units, plus the alpha of the minor lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct GridSpacing {
+    pub minor: f32,
+    pub major: f32,
+    pub minor_alpha: f32,
+}
+
+impl Grid {
+    pub fn new(steps: GridSteps) -> Grid {
+        Grid {
+            steps,
+            ..Grid::default()
+        }
+    }
+
+    fn spacing(&self, pixels_per_unit: f32) -> GridSpacing {
+        self.steps.spacing(self.min_spacing, pixels_per_unit)
+    }
+
+    /// Spacing of the visible grid lines at the given zoom, minor lines only
+    /// count once they are mostly faded in.
+    pub fn snap_spacing(&self, pixels_per_unit: f32) -> f32 {
+        let spacing = self.spacing(pixels_per_unit);
+        if spacing.minor_alpha >= 0.5 {
+            spacing.minor
+        } else {
+            spacing.major
+        }
+    }
+
+    /// Draws the coordinates of the major lines next to the axes, or at the
+    /// border of the screen if an axis is not visible.
+    pub fn draw_labels(&self, ui: &Ui, canvas: &Canvas) {
+        if !self.labels {
+            return;
+        }
+
+        let view_transform = canvas.view_transform();
+        let screen_to_canvas = view_transform.inverse().unwrap();
+        let pixels_per_unit = pixels_per_unit(view_transform, canvas.dimensions().width);
+        let major = self.spacing(pixels_per_unit).major;
+        let decimals = self.steps.decimals(major);
+
+        let min = screen_to_canvas.transform_point(Point2D::new(-1., -1.));
+        let max = screen_to_canvas.transform_point(Point2D::new(1., 1.));
+        let [left, top, width, height] = ui_rect(ui, canvas);
+        let to_ui = |p: Point2D<f32, CanvasSpace>| {
+            let p = view_transform.transform_point(p);
+            [
+                left + (p.x + 1.) / 2. * width,
+                top + (1. - p.y) / 2. * height,
+            ]
+        };
+        let font_size = ui.current_font_size();
+        let origin = to_ui(Point2D::origin());
+        let label_y = origin[1].max(top).min(top + height - font_size);
+        let label_x = origin[0].max(left).min(left + width - 4. * font_size);
+
+        let sv = ui.push_style_vars(&[StyleVar::WindowPadding([0., 0.])]);
+        Window::new(&im_str!("##GridLabels_{}", canvas.id()))
+            .position([left, top], Condition::Always)
+            .size([width, height], Condition::Always)
+            .no_decoration()
+            .no_inputs()
+            .draw_background(false)
+            .bring_to_front_on_focus(false)
+            .build(ui, || {
+                let draw_list = ui.get_window_draw_list();
+                let color = self.axis_color.to_rgba_array();
+
+                for x in multiples(min.x, max.x, major) {
+                    let text = format!("{:.*}", decimals, x);
+                    draw_list.add_text([to_ui(Point2D::new(x, 0.))[0] + 2., label_y], color, text);
+                }
+
+                for y in multiples(min.y, max.y, major) {
+                    if y.abs() > major / 2. {
+                        let text = format!("{:.*}", decimals, y);
+                        draw_list.add_text(
+                            [label_x + 2., to_ui(Point2D::new(0., y))[1]],
+                            color,
+                            text,
+                        );
+                    }
+                }
+            });
+        sv.pop(ui);
+    }
+}
+
+/// Multiples of `step` from `min` to `max`. They are counted in integers,
+/// so a step too small to change large coordinates in `f32` can't loop
+/// forever, and there are at most [`MAX_LABELS`].
+fn multiples(min: f32, max: f32, step: f32) -> impl Iterator<Item = f32> {
+    let first = (min / step).ceil() as i64;
+    let last = (max / step).floor() as i64;
+    (first..=last)
+        .take(MAX_LABELS)
+        .map(move |i| i as f32 * step)
+}
+
+/// The part of the ImGui display the canvas covers, as `[left, top, width,
+/// height]` in ImGui coordinates.
+pub(crate) fn ui_rect(ui: &Ui, canvas: &Canvas) -> [f32; 4] {
+    let [sx, sy] = ui.io().display_framebuffer_scale;
+    match canvas.viewport() {
+        Some(v) => {
+            let v = v.to_f32();
+            [v.min.x / sx, v.min.y / sy, v.width() / sx, v.height() / sy]
+        }
+        None => {
+            let [width, height] = ui.io().display_size;
+            [0., 0., width, height]
+        }
+    }
+}
+
+pub(crate) fn pixels_per_unit(
+    view_transform: Transform2D<f32, CanvasSpace, ScreenSpace>,
+    width: u32,
+) -> f32 {
+    view_transform.m11 * width as f32 / 2.
+}
+
+impl CanvasObject for Grid {
+    fn draw<'a>(&self, ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+        let spacing = self.spacing(pixels_per_unit(ctx.view_transform, ctx.dimensions.0));
+
+        ctx.programs.draw_grid(
+            ctx.target()?,
+            (spacing.minor, spacing.major),
+            spacing.minor_alpha,
+            self.minor_color,
+            self.major_color,
+            self.axis_color,
+        )?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn it_adapts_the_spacing_to_the_zoom() {
+        let grid = Grid::new(GridSteps::PowersOfTen);
+        assert_eq!(
+            grid.spacing(8.),
+            GridSpacing {
+                minor: 1.,
+                major: 10.,
+                minor_alpha: 0.
+            }
+        );
+        assert_eq!(grid.spacing(0.8).minor, 10.);
+        assert_eq!(grid.spacing(79.).minor, 1.);
+        assert!(grid.spacing(79.).minor_alpha > 0.9);
+        assert!(grid.spacing(81.).minor_alpha < 0.01);
+
+        let grid = Grid::new(GridSteps::PowersOfTwo);
+        let spacing = grid.spacing(3.);
+        assert_eq!(spacing.minor, 4.);
+        assert_eq!(spacing.major, 16.);
+        assert_eq!(spacing.minor_alpha, 0.5);
+    }
+
+    #[test]
+    fn it_counts_multiples_in_integers() {
+        let xs: Vec<_> = multiples(-2.5, 3., 1.).collect();
+        assert_eq!(xs, vec![-2., -1., 0., 1., 2., 3.]);
+        assert!(multiples(0.5, 0.9, 1.).next().is_none());
+        // `x + 1 == x` in f32 here, this must still end.
+        assert_eq!(multiples(1e9, 1e9 + 1e3, 1.).count(), MAX_LABELS);
+    }
+
+    #[test]
+    fn it_prints_enough_decimals() {
+        assert_eq!(GridSteps::PowersOfTen.decimals(10.), 0);
+        assert_eq!(GridSteps::PowersOfTen.decimals(0.01), 2);
+        assert_eq!(GridSteps::PowersOfTwo.decimals(0.125), 3);
+    }
+}
@@ -0,0 +1,394 @@
+use std::cell::{Ref, RefCell};
+
+use euclid::{Box2D, Point2D, SideOffsets2D};
+
+use crate::canvas::{union_bounds, CanvasError, CanvasObject, DrawingContext};
+use crate::geometry::quadtree::QuadTree;
+use crate::geometry::ModelSpace;
+
+/// Identifies a layer of [`Layers`]. Unlike its index it stays the same when
+/// other layers are removed or the layers are reordered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LayerId(u64);
+
+pub struct Layer {
+    id: LayerId,
+    pub name: String,
+    pub visible: bool,
+    /// Locked layers are still drawn but left out of [`Layers::pickable`],
+    /// [`Layers::pick`] and [`Layers::select`].
+    pub locked: bool,
+    pub opacity: f32,
+    object: Box<dyn CanvasObject>,
+}
+
+impl Layer {
+    pub fn id(&self) -> LayerId {
+        self.id
+    }
+
+    pub fn object(&self) -> &dyn CanvasObject {
+        &*self.object
+    }
+
+    /// The object of this layer if it is a `T`.
+    pub fn get<T: CanvasObject>(&self) -> Option<&T> {
+        self.object.as_any().downcast_ref()
+    }
+
+    pub fn get_mut<T: CanvasObject>(&mut self) -> Option<&mut T> {
+        self.object.as_any_mut().downcast_mut()
+    }
+}
+
+/// Changes to [`Layers`], as emitted by the layer panel.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LayerCommand {
+    SetVisible(LayerId, bool),
+    SetLocked(LayerId, bool),
+    SetOpacity(LayerId, f32),
+    /// Moves the layer one step towards the top.
+    Raise(LayerId),
+    /// Moves the layer one step towards the bottom.
+    Lower(LayerId),
+}
+
+/// Named layers of canvas objects, drawn from the bottom up.
+///
+/// The bounds of the visible layers are kept in a [`QuadTree`] for picking,
+/// selection and skipping layers outside of the view. It is rebuilt after
+/// any change made through `&mut Layers`.
+#[derive(Default)]
+pub struct Layers {
+    layers: Vec<Layer>,
+    next_id: u64,
+    index: RefCell<Option<LayerIndex>>,
+}
+
+/// Indices of the visible layers by their bounds.
+struct LayerIndex {
+    tree: QuadTree<usize, ModelSpace>,
+    /// Layers without bounds, they are always drawn but never picked.
+    unbounded: Vec<usize>,
+}
+
+impl LayerIndex {
+    fn new(layers: &[Layer]) -> LayerIndex {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        for (i, layer) in layers.iter().enumerate().filter(|(_, l)| l.visible) {
+            match layer.object.bounds() {
+                Some(bounds) => bounded.push((bounds, i)),
+                None => unbounded.push(i),
+            }
+        }
+        let extent = union_bounds(bounded.iter().map(|&(bounds, _)| bounds))
+            .unwrap_or_else(|| Box2D::new(Point2D::origin(), Point2D::new(1., 1.)));
+        let mut tree = QuadTree::new(extent);
+        for (bounds, i) in bounded {
+            tree.insert(bounds, i);
+        }
+        LayerIndex { tree, unbounded }
+    }
+}
+
+impl Layers {
+    pub fn new() -> Layers {
+        Layers::default()
+    }
+
+    /// Adds a layer on top of the others.
+    pub fn add(&mut self, name: impl Into<String>, object: impl CanvasObject) -> &mut Layer {
+        self.changed();
+        let id = LayerId(self.next_id);
+        self.next_id += 1;
+        self.layers.push(Layer {
+            id,
+            name: name.into(),
+            visible: true,
+            locked: false,
+            opacity: 1.,
+            object: Box::new(object),
+        });
+        self.layers.last_mut().unwrap()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Layer> {
+        let index = self.position(name)?;
+        self.changed();
+        Some(self.layers.remove(index))
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|l| l.name == name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Layer> {
+        self.changed();
+        self.layers.iter_mut().find(|l| l.name == name)
+    }
+
+    /// Index of the layer from the bottom, `None` if it was removed.
+    pub fn index_of(&self, id: LayerId) -> Option<usize> {
+        self.layers.iter().position(|l| l.id == id)
+    }
+
+    pub fn by_id(&self, id: LayerId) -> Option<&Layer> {
+        self.layers.iter().find(|l| l.id == id)
+    }
+
+    pub fn by_id_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
+        self.changed();
+        self.layers.iter_mut().find(|l| l.id == id)
+    }
+
+    /// Drops the index, the layers or their bounds may have changed.
+    fn changed(&mut self) {
+        *self.index.get_mut() = None;
+    }
+
+    fn index(&self) -> Ref<'_, LayerIndex> {
+        if self.index.borrow().is_none() {
+            *self.index.borrow_mut() = Some(LayerIndex::new(&self.layers));
+        }
+        Ref::map(self.index.borrow(), |index| index.as_ref().unwrap())
+    }
+
+    /// All layers from the bottom up.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Layers that take part in hit tests, visible and unlocked ones from the
+    /// top down.
+    pub fn pickable(&self) -> impl Iterator<Item = &Layer> {
+        self.layers.iter().rev().filter(|l| l.visible && !l.locked)
+    }
+
+    /// The topmost pickable layer whose bounds are at most `radius` away
+    /// from `position`.
+    pub fn pick(&self, position: Point2D<f32, ModelSpace>, radius: f32) -> Option<LayerId> {
+        let area = Box2D::new(position, position).outer_box(SideOffsets2D::new_all_same(radius));
+        self.select(&area).first().copied()
+    }
+
+    /// The pickable layers whose bounds intersect `rect`, from the top down.
+    pub fn select(&self, rect: &Box2D<f32, ModelSpace>) -> Vec<LayerId> {
+        let mut indices: Vec<usize> = self
+            .index()
+            .tree
+            .query_rect(rect)
+            .into_iter()
+            .copied()
+            .filter(|&i| !self.layers[i].locked)
+            .collect();
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        indices.into_iter().map(|i| self.layers[i].id).collect()
+    }
+
+    /// Applies a command from the layer panel. Commands for layers that
+    /// don't exist anymore, e.g. queued before a [`remove`](Layers::remove),
+    /// are ignored.
+    pub fn apply(&mut self, command: LayerCommand) {
+        self.changed();
+        match command {
+            LayerCommand::SetVisible(id, visible) => {
+                if let Some(layer) = self.by_id_mut(id) {
+                    layer.visible = visible;
+                }
+            }
+            LayerCommand::SetLocked(id, locked) => {
+                if let Some(layer) = self.by_id_mut(id) {
+                    layer.locked = locked;
+                }
+            }
+            LayerCommand::SetOpacity(id, opacity) => {
+                if let Some(layer) = self.by_id_mut(id) {
+                    layer.opacity = opacity.clamp(0., 1.);
+                }
+            }
+            LayerCommand::Raise(id) => match self.index_of(id) {
+                Some(i) if i + 1 < self.layers.len() => self.layers.swap(i, i + 1),
+                _ => (),
+            },
+            LayerCommand::Lower(id) => match self.index_of(id) {
+                Some(i) if i > 0 => self.layers.swap(i, i - 1),
+                _ => (),
+            },
+        }
+    }
+}
+
+impl CanvasObject for Layers {
+    /// Draws the visible layers that reach into the view.
+    fn draw<'a>(&self, ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+        let mut drawn = vec![false; self.layers.len()];
+        {
+            let index = self.index();
+            for &i in &index.unbounded {
+                drawn[i] = true;
+            }
+            match ctx.model_visible_rect() {
+                Some(rect) => {
+                    for &i in index.tree.query_rect(&rect) {
+                        drawn[i] = true;
+                    }
+                }
+                None => {
+                    for (i, layer) in self.layers.iter().enumerate() {
+                        drawn[i] = layer.visible;
+                    }
+                }
+            }
+        }
+
+        for (layer, _) in self.layers.iter().zip(drawn).filter(|&(_, drawn)| drawn) {
+            ctx.with_opacity(layer.opacity, |ctx| layer.object.draw(ctx))?;
+        }
+        Ok(())
+    }
+
+    fn bounds(&self) -> Option<Box2D<f32, ModelSpace>> {
+        union_bounds(
+            self.layers
+                .iter()
+                .filter(|l| l.visible)
+                .filter_map(|l| l.object.bounds()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    struct Empty;
+
+    impl CanvasObject for Empty {
+        fn draw<'a>(&self, _ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+            Ok(())
+        }
+    }
+
+    struct Square(Box2D<f32, ModelSpace>);
+
+    impl CanvasObject for Square {
+        fn draw<'a>(&self, _ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+            Ok(())
+        }
+
+        fn bounds(&self) -> Option<Box2D<f32, ModelSpace>> {
+            Some(self.0)
+        }
+    }
+
+    fn square(x: f32, y: f32, size: f32) -> Square {
+        Square(Box2D::new(
+            Point2D::new(x, y),
+            Point2D::new(x + size, y + size),
+        ))
+    }
+
+    fn names<'a>(layers: impl Iterator<Item = &'a Layer>) -> Vec<&'a str> {
+        layers.map(|l| &l.name[..]).collect()
+    }
+
+    #[test]
+    fn it_orders_and_filters_layers() {
+        let mut layers = Layers::new();
+        let background = layers.add("background", Empty).id();
+        let geometry = layers.add("geometry", Empty).id();
+        let annotations = layers.add("annotations", Empty).id();
+        layers.by_id_mut(annotations).unwrap().locked = true;
+
+        assert_eq!(names(layers.pickable()), ["geometry", "background"]);
+
+        layers.apply(LayerCommand::Raise(background));
+        layers.apply(LayerCommand::Raise(annotations));
+        assert_eq!(
+            names(layers.layers().iter()),
+            ["geometry", "background", "annotations"]
+        );
+
+        layers.apply(LayerCommand::SetVisible(geometry, false));
+        layers.apply(LayerCommand::SetLocked(annotations, false));
+        assert_eq!(names(layers.pickable()), ["annotations", "background"]);
+
+        layers.apply(LayerCommand::SetOpacity(background, 1.5));
+        assert_eq!(layers.get("background").unwrap().opacity, 1.);
+    }
+
+    #[test]
+    fn it_ignores_stale_commands() {
+        let mut layers = Layers::new();
+        let a = layers.add("a", Empty).id();
+        let b = layers.add("b", Empty).id();
+        let c = layers.add("c", Empty).id();
+
+        // Queued before the changes, the commands still reach their layers.
+        let commands = [
+            LayerCommand::SetVisible(b, false),
+            LayerCommand::SetVisible(c, false),
+            LayerCommand::Lower(a),
+            LayerCommand::SetOpacity(a, 0.5),
+        ];
+        layers.remove("b");
+        layers.apply(LayerCommand::Raise(a));
+        for command in commands.iter().cloned() {
+            layers.apply(command);
+        }
+        assert_eq!(names(layers.layers().iter()), ["a", "c"]);
+        assert!(!layers.by_id(c).unwrap().visible);
+        assert!(layers.by_id(a).unwrap().visible);
+        assert_eq!(layers.by_id(a).unwrap().opacity, 0.5);
+        assert!(layers.by_id(b).is_none());
+        assert_eq!(layers.index_of(a), Some(0));
+    }
+
+    #[test]
+    fn it_picks_unlocked_layers() {
+        let mut layers = Layers::new();
+        let bottom = layers.add("bottom", square(0., 0., 10.)).id();
+        let top = layers.add("top", square(5., 5., 10.)).id();
+        layers.add("empty", Empty);
+
+        let at = |x, y| Point2D::new(x, y);
+        assert_eq!(layers.pick(at(7., 7.), 0.), Some(top));
+        assert_eq!(layers.pick(at(2., 2.), 0.), Some(bottom));
+        assert_eq!(layers.pick(at(-1., 2.), 0.), None);
+        assert_eq!(layers.pick(at(-1., 2.), 1.), Some(bottom));
+        let rect = Box2D::new(at(0., 0.), at(6., 6.));
+        assert_eq!(layers.select(&rect), [top, bottom]);
+
+        layers.get_mut("top").unwrap().locked = true;
+        assert_eq!(layers.pick(at(7., 7.), 0.), Some(bottom));
+        assert_eq!(layers.select(&rect), [bottom]);
+        layers.get_mut("bottom").unwrap().visible = false;
+        assert_eq!(layers.pick(at(7., 7.), 0.), None);
+    }
+
+    #[test]
+    fn it_indexes_moved_layers() {
+        let mut layers = Layers::new();
+        let ids: Vec<_> = (0..100)
+            .map(|i| {
+                let (x, y) = ((i % 10) as f32 * 20., (i / 10) as f32 * 20.);
+                layers.add(format!("{}", i), square(x, y, 10.)).id()
+            })
+            .collect();
+        let at = |x, y| Point2D::new(x, y);
+        assert_eq!(layers.pick(at(45., 65.), 0.), Some(ids[32]));
+        assert_eq!(layers.select(&Box2D::new(at(12., 12.), at(38., 18.))), []);
+
+        *layers.get_mut("32").unwrap().get_mut::<Square>().unwrap() = square(500., 500., 10.);
+        assert_eq!(layers.pick(at(45., 65.), 0.), None);
+        assert_eq!(layers.pick(at(505., 505.), 0.), Some(ids[32]));
+        layers.remove("32");
+        assert_eq!(layers.pick(at(505., 505.), 0.), None);
+    }
+}
@@ -5,9 +5,9 @@ use std::{
 };
 
 use canvas::Canvas;
-use euclid::Point2D;
+use euclid::{Box2D, Point2D};
 use event_handling::{EventHandler, FnEventHandler, Imgui};
-use geometry::CanvasSpace;
+use geometry::{CanvasSpace, PixelSpace};
 use glium::{
     glutin::{
         dpi::LogicalSize,
@@ -18,11 +18,17 @@ use glium::{
 };
 
 use log::info;
+use state::{CommandRequest, UndoShortcuts};
 
 pub mod canvas;
 pub mod event_handling;
 pub mod geometry;
 pub mod graphics;
+pub mod grid;
+pub mod layers;
+pub mod scene;
+pub mod snap;
+pub mod state;
 pub mod texture;
 pub mod ui;
 
@@ -30,7 +36,14 @@ pub struct AppInit {
     pub display: Display,
     pub event_loop: Option<EventLoop<()>>,
     pub canvas: Canvas,
+    /// Further canvases with their own viewport and camera, e.g. for split
+    /// views. Input goes to the view under the cursor before `canvas`.
+    pub views: Vec<Canvas>,
     pub imgui: Rc<RefCell<Imgui>>,
+    /// Undo and redo requests from the keyboard shortcuts, apply them to
+    /// your [`State`](state::State) while drawing.
+    pub command_requests: RefCell<Vec<CommandRequest>>,
+    undo_shortcuts: UndoShortcuts,
 }
 
 impl AppInit {
@@ -51,14 +64,32 @@ impl AppInit {
             display,
             event_loop: Some(event_loop),
             canvas,
+            views: Vec::new(),
             imgui,
+            command_requests: RefCell::new(Vec::new()),
+            undo_shortcuts: UndoShortcuts::new(),
         }
     }
 
-    pub fn set_canvas_click_handler(&mut self, handler: Box<dyn FnMut(Point2D<f32, CanvasSpace>, MouseButton)>) {
+    pub fn set_canvas_click_handler(&mut self, handler: Box<dyn FnMut(Point2D<f64, CanvasSpace>, MouseButton)>) {
         self.canvas.set_click_handler(handler);
     }
 
+    /// Adds a canvas covering `viewport`, given in window pixels from the top
+    /// left. Draw it like the main canvas, it only draws inside its viewport.
+    /// It shares the compiled programs of the main canvas.
+    pub fn add_view(&mut self, viewport: Box2D<u32, PixelSpace>) -> &mut Canvas {
+        let mut canvas = Canvas::new_sharing(&self.display, &self.canvas);
+        canvas.set_viewport(Some(viewport));
+        self.views.push(canvas);
+        self.views.last_mut().unwrap()
+    }
+
+    /// Takes the requests made since the last call.
+    pub fn take_command_requests(&self) -> Vec<CommandRequest> {
+        self.command_requests.replace(Vec::new())
+    }
+
     pub fn run(mut self, mut draw: impl FnMut(&AppInit, &mut Frame, Instant) + 'static) {
         let event_loop = self.event_loop.take().expect("No event loop");
 
@@ -66,6 +97,7 @@ impl AppInit {
         let mut last_frame = Instant::now();
         event_loop.run(move |event, _target, control| {
             let canvas_input = self.canvas.input();
+            let view_inputs: Vec<_> = self.views.iter_mut().map(Canvas::input).collect();
 
             // Handle close,
             FnEventHandler(|e| {
@@ -80,11 +112,28 @@ impl AppInit {
                 }
             })
             // Handle captured mouse/keyboard (moving points, etc.)
+            .chain(view_inputs.iter().map(|i| i.capture()).collect::<Vec<_>>())
             .chain(canvas_input.capture())
             // Handle GUI
             .chain(&mut *self.imgui.borrow_mut())
-            // TODO: Handle shortcuts
+            // Handle shortcuts
+            .chain(self.undo_shortcuts.handler(&self.command_requests))
+            // The views take the cursor from the canvas below them
+            .chain(FnEventHandler(|e| {
+                if let Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    ..
+                } = e
+                {
+                    let position = Point2D::new(position.x as f32, position.y as f32);
+                    if view_inputs.iter().any(|i| i.contains(position)) {
+                        canvas_input.cover_cursor(position);
+                    }
+                }
+                false
+            }))
             // Handle interactions with canvas
+            .chain(view_inputs.iter().map(|i| i.usual()).collect::<Vec<_>>())
             .chain(canvas_input.usual())
             .handle_event(&event);
 
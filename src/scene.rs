@@ -0,0 +1,256 @@
+use euclid::{Box2D, Transform2D};
+
+use crate::canvas::{union_bounds, CanvasError, CanvasObject, DrawingContext};
+use crate::geometry::{CanvasSpace, ModelSpace};
+
+/// Node of a retained scene. A node optionally holds an object and any number
+/// of children, all drawn with the node's transform applied on top of the
+/// transforms of its parents.
+///
+/// The root node is the scene, pass it to [`Canvas::draw`](crate::canvas::Canvas::draw).
+pub struct SceneNode {
+    pub name: String,
+    /// Transform relative to the parent node.
+    pub transform: Transform2D<f32, ModelSpace, CanvasSpace>,
+    /// Hidden nodes are skipped with all their children.
+    pub visible: bool,
+    /// Children are drawn from low to high z, equal z in insertion order.
+    pub z: i32,
+    object: Option<Box<dyn CanvasObject>>,
+    children: Vec<SceneNode>,
+}
+
+impl SceneNode {
+    /// Creates an empty node to group other nodes.
+    pub fn new(name: impl Into<String>) -> SceneNode {
+        SceneNode {
+            name: name.into(),
+            transform: Transform2D::identity(),
+            visible: true,
+            z: 0,
+            object: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn with_object(name: impl Into<String>, object: impl CanvasObject) -> SceneNode {
+        SceneNode {
+            object: Some(Box::new(object)),
+            ..SceneNode::new(name)
+        }
+    }
+
+    pub fn with_transform(self, transform: Transform2D<f32, ModelSpace, CanvasSpace>) -> SceneNode {
+        SceneNode { transform, ..self }
+    }
+
+    pub fn with_z(self, z: i32) -> SceneNode {
+        SceneNode { z, ..self }
+    }
+
+    pub fn object(&self) -> Option<&dyn CanvasObject> {
+        self.object.as_deref()
+    }
+
+    pub fn set_object(&mut self, object: Option<Box<dyn CanvasObject>>) {
+        self.object = object;
+    }
+
+    /// The object of this node if it is a `T`.
+    pub fn get<T: CanvasObject>(&self) -> Option<&T> {
+        self.object.as_ref()?.as_any().downcast_ref()
+    }
+
+    pub fn get_mut<T: CanvasObject>(&mut self) -> Option<&mut T> {
+        self.object.as_mut()?.as_any_mut().downcast_mut()
+    }
+
+    pub fn add_child(&mut self, child: SceneNode) -> &mut SceneNode {
+        self.children.push(child);
+        self.children.last_mut().unwrap()
+    }
+
+    pub fn remove_child(&mut self, name: &str) -> Option<SceneNode> {
+        let index = self.children.iter().position(|c| c.name == name)?;
+        Some(self.children.remove(index))
+    }
+
+    pub fn children(&self) -> &[SceneNode] {
+        &self.children
+    }
+
+    pub fn children_mut(&mut self) -> &mut [SceneNode] {
+        &mut self.children
+    }
+
+    /// Finds a descendant by the names on the way to it, separated by `/`.
+    pub fn find(&self, path: &str) -> Option<&SceneNode> {
+        path.split('/').try_fold(self, |node, name| {
+            node.children.iter().find(|c| c.name == name)
+        })
+    }
+
+    pub fn find_mut(&mut self, path: &str) -> Option<&mut SceneNode> {
+        path.split('/').try_fold(self, |node, name| {
+            node.children.iter_mut().find(|c| c.name == name)
+        })
+    }
+
+    /// Transform from the space of the descendant at `path` to the space of
+    /// this node, including the transform of this node.
+    pub fn transform_of(&self, path: &str) -> Option<Transform2D<f32, ModelSpace, CanvasSpace>> {
+        let mut node = self;
+        let mut transform = self.transform;
+        for name in path.split('/') {
+            node = node.children.iter().find(|c| c.name == name)?;
+            transform = node
+                .transform
+                .with_destination::<ModelSpace>()
+                .then(&transform);
+        }
+        Some(transform)
+    }
+
+    /// Visible children in the order they are drawn.
+    fn draw_order(&self) -> Vec<&SceneNode> {
+        let mut children: Vec<_> = self.children.iter().filter(|c| c.visible).collect();
+        children.sort_by_key(|c| c.z);
+        children
+    }
+}
+
+impl CanvasObject for SceneNode {
+    fn draw<'a>(&self, ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        ctx.with_model_transform(self.transform.cast(), |mut ctx| {
+            if let Some(object) = &self.object {
+                // Objects outside of the view are skipped, children are
+                // checked on their own as their bounds may be unknown.
+                if object.bounds().map_or(true, |b| ctx.is_visible(&b)) {
+                    object.draw(&mut ctx)?;
+                }
+            }
+            for child in self.draw_order() {
+                child.draw(&mut ctx)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Bounds of the visible objects, including the transform of this node.
+    fn bounds(&self) -> Option<Box2D<f32, ModelSpace>> {
+        if !self.visible {
+            return None;
+        }
+
+        let own = self.object.as_ref().and_then(|o| o.bounds());
+        let children = self.children.iter().filter_map(|c| c.bounds());
+        let local = union_bounds(own.into_iter().chain(children))?;
+        Some(
+            self.transform
+                .with_destination::<ModelSpace>()
+                .outer_transformed_box(&local),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use euclid::Point2D;
+
+    use super::*;
+
+    struct Marker(u32);
+
+    impl CanvasObject for Marker {
+        fn draw<'a>(&self, _ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+            Ok(())
+        }
+    }
+
+    fn scene() -> SceneNode {
+        let mut root = SceneNode::new("root");
+        let group = root
+            .add_child(SceneNode::new("group").with_transform(Transform2D::translation(10., 0.)));
+        group.add_child(
+            SceneNode::with_object("a", Marker(1))
+                .with_transform(Transform2D::scale(2., 2.))
+                .with_z(1),
+        );
+        group.add_child(SceneNode::with_object("b", Marker(2)));
+        root
+    }
+
+    #[test]
+    fn it_finds_nodes_by_path() {
+        let mut root = scene();
+        assert_eq!(root.find("group/a").unwrap().get::<Marker>().unwrap().0, 1);
+        assert!(root.find("group/c").is_none());
+
+        root.find_mut("group/b")
+            .unwrap()
+            .get_mut::<Marker>()
+            .unwrap()
+            .0 = 3;
+        assert_eq!(root.find("group/b").unwrap().get::<Marker>().unwrap().0, 3);
+    }
+
+    #[test]
+    fn it_composes_transforms() {
+        let root = scene();
+        let transform = root.transform_of("group/a").unwrap();
+        assert_eq!(
+            transform.transform_point(Point2D::new(1., 1.)),
+            Point2D::new(12., 2.)
+        );
+    }
+
+    #[test]
+    fn it_unions_the_bounds_of_visible_children() {
+        struct Square;
+
+        impl CanvasObject for Square {
+            fn draw<'a>(&self, _ctx: &mut DrawingContext<'a>) -> Result<(), CanvasError> {
+                Ok(())
+            }
+
+            fn bounds(&self) -> Option<Box2D<f32, ModelSpace>> {
+                Some(Box2D::new(Point2D::new(0., 0.), Point2D::new(1., 1.)))
+            }
+        }
+
+        let mut root = scene();
+        assert_eq!(root.bounds(), None);
+
+        root.add_child(SceneNode::with_object("square", Square));
+        root.find_mut("group/a")
+            .unwrap()
+            .set_object(Some(Box::new(Square)));
+        assert_eq!(
+            root.bounds(),
+            Some(Box2D::new(Point2D::new(0., 0.), Point2D::new(12., 2.)))
+        );
+
+        root.find_mut("group").unwrap().visible = false;
+        assert_eq!(
+            root.bounds(),
+            Some(Box2D::new(Point2D::new(0., 0.), Point2D::new(1., 1.)))
+        );
+    }
+
+    #[test]
+    fn it_draws_by_z_and_skips_hidden_nodes() {
+        let mut root = scene();
+        let group = root.find("group").unwrap();
+        let names: Vec<_> = group.draw_order().iter().map(|c| &c.name[..]).collect();
+        assert_eq!(names, ["b", "a"]);
+
+        root.find_mut("group/b").unwrap().visible = false;
+        let group = root.find("group").unwrap();
+        assert_eq!(group.draw_order().len(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+use euclid::{Point2D, Vector2D};
+use glium::glutin::event::VirtualKeyCode;
+
+use crate::geometry::{CanvasSpace, Line};
+use crate::graphics::primitives::{Color, Line as GLLine};
+use crate::grid::Grid;
+
+/// What a position was snapped to, in order of priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapKind {
+    Point,
+    Endpoint,
+    Intersection,
+    Midpoint,
+    Grid,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Snap {
+    pub position: Point2D<f32, CanvasSpace>,
+    pub kind: SnapKind,
+}
+
+/// Moves positions onto registered geometry or the grid. The canvas runs every
+/// click through it and shows where the cursor would snap to.
+///
+/// Geometry is only snapped to within `radius` pixels, the grid (if set)
+/// catches everything else.
+pub struct Snapper {
+    pub enabled: bool,
+    /// Key that turns snapping on and off.
+    pub toggle_key: VirtualKeyCode,
+    /// Maximum distance to snap to geometry, in pixels.
+    pub radius: f32,
+    /// Snap to the lines of this grid, using the spacing it shows at the
+    /// current zoom.
+    pub grid: Option<Grid>,
+    pub color: Color,
+    points: Vec<Point2D<f32, CanvasSpace>>,
+    segments: Vec<Line<CanvasSpace>>,
+    intersections: Vec<Point2D<f32, CanvasSpace>>,
+}
+
+impl Default for Snapper {
+    fn default() -> Snapper {
+        Snapper {
+            enabled: true,
+            toggle_key: VirtualKeyCode::F9,
+            radius: 10.,
+            grid: None,
+            color: Color::ORANGE_RED,
+            points: Vec::new(),
+            segments: Vec::new(),
+            intersections: Vec::new(),
+        }
+    }
+}
+
+impl Snapper {
+    pub fn add_point(&mut self, point: Point2D<f32, CanvasSpace>) {
+        self.points.push(point);
+    }
+
+    /// Registers a segment, its endpoints, midpoint and intersections with
+    /// the other segments become snap targets.
+    pub fn add_segment(&mut self, segment: Line<CanvasSpace>) {
+        let intersections = self.segments.iter().filter_map(|s| s.intersect(&segment));
+        self.intersections.extend(intersections);
+        self.segments.push(segment);
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+        self.segments.clear();
+        self.intersections.clear();
+    }
+
+    /// Snaps `position`, `None` if snapping is disabled or nothing is close.
+    pub fn snap(&self, position: Point2D<f32, CanvasSpace>, pixels_per_unit: f32) -> Option<Snap> {
+        if !self.enabled {
+            return None;
+        }
+
+        let radius = self.radius / pixels_per_unit;
+        let nearest =
+            |kind: SnapKind, points: &mut dyn Iterator<Item = Point2D<f32, CanvasSpace>>| {
+                points
+                    .map(|p| (p.distance_to(position), p))
+                    .filter(|(d, _)| *d <= radius)
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+                    .map(|(_, position)| Snap { position, kind })
+            };
+
+        nearest(SnapKind::Point, &mut self.points.iter().copied())
+            .or_else(|| {
+                let mut endpoints = self.segments.iter().flat_map(|s| vec![s.start, s.end]);
+                nearest(SnapKind::Endpoint, &mut endpoints)
+            })
+            .or_else(|| {
+                nearest(
+                    SnapKind::Intersection,
+                    &mut self.intersections.iter().copied(),
+                )
+            })
+            .or_else(|| {
+                nearest(
+                    SnapKind::Midpoint,
+                    &mut self.segments.iter().map(Line::midpoint),
+                )
+            })
+            .or_else(|| {
+                let spacing = self.grid.as_ref()?.snap_spacing(pixels_per_unit);
+                Some(Snap {
+                    position: (position / spacing).round() * spacing,
+                    kind: SnapKind::Grid,
+                })
+            })
+    }
+
+    /// Lines marking a snap, about `size` pixels across. Each kind has its own
+    /// shape so it's clear what the cursor snaps to.
+    pub fn indicator(snap: Snap, size: f32, pixels_per_unit: f32) -> Vec<GLLine> {
+        let r = size / 2. / pixels_per_unit;
+        let p = snap.position;
+        let v = |x: f32, y: f32| p + Vector2D::new(x, y) * r;
+        let outline = |corners: &[Point2D<f32, CanvasSpace>]| {
+            let next = corners.iter().cycle().skip(1);
+            corners
+                .iter()
+                .zip(next)
+                .map(|(a, b)| GLLine::from_points(*a, *b))
+                .collect()
+        };
+
+        match snap.kind {
+            SnapKind::Point | SnapKind::Endpoint => {
+                outline(&[v(-1., -1.), v(1., -1.), v(1., 1.), v(-1., 1.)])
+            }
+            SnapKind::Midpoint => outline(&[v(-1., -0.8), v(1., -0.8), v(0., 1.)]),
+            SnapKind::Intersection => vec![
+                GLLine::from_points(v(-1., -1.), v(1., 1.)),
+                GLLine::from_points(v(-1., 1.), v(1., -1.)),
+            ],
+            SnapKind::Grid => vec![
+                GLLine::from_points(v(-0.5, 0.), v(0.5, 0.)),
+                GLLine::from_points(v(0., -0.5), v(0., 0.5)),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn it_prefers_geometry_over_the_grid() {
+        let mut snapper = Snapper {
+            grid: Some(Grid::default()),
+            ..Snapper::default()
+        };
+        snapper.add_segment(Line::new(0., 0., 10., 0.));
+        snapper.add_segment(Line::new(4., -4., 4., 4.));
+
+        let snap = |x, y| snapper.snap(Point2D::new(x, y), 100.).unwrap();
+        assert_eq!(snap(0.05, 0.05).kind, SnapKind::Endpoint);
+        assert_eq!(snap(0.05, 0.05).position, Point2D::new(0., 0.));
+        assert_eq!(snap(4.05, 0.03).kind, SnapKind::Intersection);
+        assert_eq!(snap(4.05, 0.03).position, Point2D::new(4., 0.));
+        assert_eq!(snap(5.02, -0.01).kind, SnapKind::Midpoint);
+        assert_eq!(
+            snap(7.3, 2.6),
+            Snap {
+                position: Point2D::new(7., 3.),
+                kind: SnapKind::Grid
+            }
+        );
+
+        snapper.enabled = false;
+        assert_eq!(snapper.snap(Point2D::new(0., 0.), 100.), None);
+    }
+}
@@ -0,0 +1,436 @@
+use std::any::Any;
+use std::cell::RefCell;
+
+use glium::glutin::event::{
+    ElementState, Event, KeyboardInput, ModifiersState, VirtualKeyCode, WindowEvent,
+};
+
+use crate::event_handling::{EventHandler, FnEventHandler};
+
+/// A reversible change of a state `S`.
+pub trait Command<S>: Any + CommandExt {
+    fn apply(&mut self, state: &mut S);
+    fn revert(&mut self, state: &mut S);
+
+    /// Shown in the history.
+    fn description(&self) -> String;
+
+    /// Folds `next`, which was just applied after this command, into this
+    /// command so both are undone in one step, e.g. the steps of a drag.
+    /// Returns false if they can't be merged.
+    fn merge(&mut self, _next: &dyn Command<S>) -> bool {
+        false
+    }
+}
+
+pub trait CommandExt: Any {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> CommandExt for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Requests to move through the history, as emitted by the undo shortcuts
+/// and the history panel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommandRequest {
+    Undo,
+    Redo,
+    /// Undoes or redoes entries until `n` of them are applied.
+    Goto(usize),
+}
+
+/// One step of the history, a single command or a group of them.
+struct Entry<S> {
+    /// Set for groups, single commands describe themselves as they may
+    /// change when merged.
+    description: Option<String>,
+    commands: Vec<Box<dyn Command<S>>>,
+}
+
+impl<S: 'static> Entry<S> {
+    fn description(&self) -> String {
+        match (&self.description, &self.commands[..]) {
+            (Some(description), _) => description.clone(),
+            (None, [command]) => command.description(),
+            (None, _) => String::new(),
+        }
+    }
+
+    fn apply(&mut self, state: &mut S) {
+        for command in &mut self.commands {
+            command.apply(state);
+        }
+    }
+
+    fn revert(&mut self, state: &mut S) {
+        for command in self.commands.iter_mut().rev() {
+            command.revert(state);
+        }
+    }
+}
+
+/// Applied commands that can be undone and redone.
+pub struct UndoStack<S> {
+    entries: Vec<Entry<S>>,
+    /// Number of applied entries, the ones after it can be redone.
+    position: usize,
+    /// Oldest entries are dropped once there are more than this.
+    pub limit: Option<usize>,
+    group: Option<Entry<S>>,
+    group_depth: usize,
+    sealed: bool,
+}
+
+impl<S> Default for UndoStack<S> {
+    fn default() -> Self {
+        UndoStack {
+            entries: Vec::new(),
+            position: 0,
+            limit: Some(100),
+            group: None,
+            group_depth: 0,
+            sealed: false,
+        }
+    }
+}
+
+impl<S: 'static> UndoStack<S> {
+    pub fn new() -> UndoStack<S> {
+        UndoStack::default()
+    }
+
+    /// Applies `command` and records it, dropping everything that could be
+    /// redone.
+    pub fn execute(&mut self, state: &mut S, mut command: Box<dyn Command<S>>) {
+        command.apply(state);
+
+        if let Some(group) = &mut self.group {
+            if !self.sealed {
+                if let Some(last) = group.commands.last_mut() {
+                    if last.merge(&*command) {
+                        return;
+                    }
+                }
+            }
+            group.commands.push(command);
+            self.sealed = false;
+            return;
+        }
+
+        let branched = self.position < self.entries.len();
+        self.entries.truncate(self.position);
+        if !branched && !self.sealed {
+            if let Some(Entry { commands, .. }) = self.entries.last_mut() {
+                if commands.len() == 1 && commands[0].merge(&*command) {
+                    return;
+                }
+            }
+        }
+        self.push(Entry {
+            description: None,
+            commands: vec![command],
+        });
+    }
+
+    fn push(&mut self, entry: Entry<S>) {
+        self.entries.truncate(self.position);
+        self.entries.push(entry);
+        if let Some(limit) = self.limit {
+            let excess = self.entries.len().saturating_sub(limit);
+            self.entries.drain(..excess);
+        }
+        self.position = self.entries.len();
+        self.sealed = false;
+    }
+
+    /// Keeps the next command from being merged into the last one, e.g. when
+    /// a drag ends.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Records the commands executed until the matching [`end_group`] as one
+    /// entry. Groups can be nested, only the outermost one is recorded.
+    ///
+    /// [`end_group`]: UndoStack::end_group
+    pub fn begin_group(&mut self, description: impl Into<String>) {
+        self.group_depth += 1;
+        if self.group.is_none() {
+            self.group = Some(Entry {
+                description: Some(description.into()),
+                commands: Vec::new(),
+            });
+            self.sealed = true;
+        }
+    }
+
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+        if self.group_depth > 0 {
+            return;
+        }
+        match self.group.take() {
+            Some(group) if !group.commands.is_empty() => self.push(group),
+            _ => (),
+        }
+        self.sealed = true;
+    }
+
+    fn close_groups(&mut self) {
+        if self.group.is_some() {
+            self.group_depth = 1;
+            self.end_group();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.position > 0 || self.group.as_ref().is_some_and(|g| !g.commands.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.group.is_none() && self.position < self.entries.len()
+    }
+
+    /// Undoes the last entry, closing open groups first.
+    pub fn undo(&mut self, state: &mut S) -> bool {
+        self.close_groups();
+        if self.position == 0 {
+            return false;
+        }
+        self.position -= 1;
+        self.entries[self.position].revert(state);
+        self.sealed = true;
+        true
+    }
+
+    pub fn redo(&mut self, state: &mut S) -> bool {
+        self.close_groups();
+        if self.position == self.entries.len() {
+            return false;
+        }
+        self.entries[self.position].apply(state);
+        self.position += 1;
+        self.sealed = true;
+        true
+    }
+
+    /// Undoes or redoes entries until `position` of them are applied.
+    pub fn goto(&mut self, state: &mut S, position: usize) {
+        let position = position.min(self.entries.len());
+        while self.position > position && self.undo(state) {}
+        while self.position < position && self.redo(state) {}
+    }
+
+    pub fn request(&mut self, state: &mut S, request: CommandRequest) {
+        match request {
+            CommandRequest::Undo => {
+                self.undo(state);
+            }
+            CommandRequest::Redo => {
+                self.redo(state);
+            }
+            CommandRequest::Goto(position) => self.goto(state, position),
+        }
+    }
+
+    /// Number of applied entries.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Descriptions of all entries from the oldest, including the ones that
+    /// can be redone.
+    pub fn descriptions(&self) -> impl Iterator<Item = String> + '_ {
+        self.entries.iter().map(Entry::description)
+    }
+
+    /// Forgets the history, the state is left as it is.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.position = 0;
+        self.group = None;
+        self.group_depth = 0;
+    }
+}
+
+/// Data changed only through commands, together with its history.
+pub struct State<S> {
+    data: S,
+    pub history: UndoStack<S>,
+}
+
+impl<S: 'static> State<S> {
+    pub fn new(data: S) -> State<S> {
+        State {
+            data,
+            history: UndoStack::new(),
+        }
+    }
+
+    pub fn data(&self) -> &S {
+        &self.data
+    }
+
+    pub fn execute(&mut self, command: impl Command<S>) {
+        self.history.execute(&mut self.data, Box::new(command));
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.history.undo(&mut self.data)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.history.redo(&mut self.data)
+    }
+
+    pub fn request(&mut self, request: CommandRequest) {
+        self.history.request(&mut self.data, request);
+    }
+}
+
+/// Turns Ctrl+Z into [`CommandRequest::Undo`] and Ctrl+Y or Ctrl+Shift+Z
+/// into [`CommandRequest::Redo`].
+#[derive(Default)]
+pub struct UndoShortcuts {
+    modifiers: ModifiersState,
+}
+
+impl UndoShortcuts {
+    pub fn new() -> UndoShortcuts {
+        UndoShortcuts::default()
+    }
+
+    pub fn handler<'a>(
+        &'a mut self,
+        requests: &'a RefCell<Vec<CommandRequest>>,
+    ) -> impl EventHandler + 'a {
+        FnEventHandler(move |event| {
+            let event = if let Event::WindowEvent { event, .. } = event {
+                event
+            } else {
+                return false;
+            };
+
+            match event {
+                WindowEvent::ModifiersChanged(modifiers) => {
+                    self.modifiers = *modifiers;
+                    false
+                }
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } if self.modifiers.ctrl() && !self.modifiers.alt() => {
+                    let request = match (self.modifiers.shift(), key) {
+                        (false, VirtualKeyCode::Z) => CommandRequest::Undo,
+                        (true, VirtualKeyCode::Z) | (false, VirtualKeyCode::Y) => {
+                            CommandRequest::Redo
+                        }
+                        _ => return false,
+                    };
+                    requests.borrow_mut().push(request);
+                    true
+                }
+                _ => false,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    struct Add(i32);
+
+    impl Command<i32> for Add {
+        fn apply(&mut self, state: &mut i32) {
+            *state += self.0;
+        }
+
+        fn revert(&mut self, state: &mut i32) {
+            *state -= self.0;
+        }
+
+        fn description(&self) -> String {
+            format!("Add {}", self.0)
+        }
+
+        fn merge(&mut self, next: &dyn Command<i32>) -> bool {
+            match next.as_any().downcast_ref::<Add>() {
+                Some(next) if self.0.signum() == next.0.signum() => {
+                    self.0 += next.0;
+                    true
+                }
+                _ => false,
+            }
+        }
+    }
+
+    #[test]
+    fn it_undoes_and_redoes_merged_commands() {
+        let mut state = State::new(0);
+        state.execute(Add(1));
+        state.execute(Add(2));
+        state.history.seal();
+        state.execute(Add(3));
+        state.execute(Add(-4));
+        assert_eq!(*state.data(), 2);
+        assert_eq!(
+            state.history.descriptions().collect::<Vec<_>>(),
+            ["Add 3", "Add 3", "Add -4"]
+        );
+
+        assert!(state.undo());
+        assert!(state.undo());
+        assert_eq!(*state.data(), 3);
+        assert!(state.redo());
+        assert_eq!(*state.data(), 6);
+
+        // A new command drops what could be redone and doesn't merge.
+        state.execute(Add(5));
+        assert_eq!(state.history.len(), 3);
+        assert!(!state.redo());
+
+        state.request(CommandRequest::Goto(0));
+        assert_eq!(*state.data(), 0);
+    }
+
+    #[test]
+    fn it_groups_commands_and_limits_the_history() {
+        let mut state = State::new(0);
+        state.history.limit = Some(2);
+        state.history.begin_group("Move");
+        state.execute(Add(1));
+        state.history.begin_group("Nested");
+        state.execute(Add(-1));
+        state.execute(Add(10));
+        state.history.end_group();
+        assert_eq!(state.history.len(), 0);
+        state.history.end_group();
+        assert_eq!(state.history.descriptions().collect::<Vec<_>>(), ["Move"]);
+
+        state.execute(Add(-2));
+        state.execute(Add(3));
+        assert_eq!(state.history.len(), 2);
+        state.history.goto(&mut state.data, 0);
+        assert_eq!(state.data, 10);
+    }
+}
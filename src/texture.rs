@@ -9,11 +9,6 @@ pub fn load_png_texture(display: &Display, bytes: &[u8]) -> Texture2d {
         .to_rgba8();
     let image_dimensions = image.dimensions();
     let image = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
-    let texture = glium::texture::Texture2d::with_mipmaps(
-        display,
-        image,
-        MipmapsOption::AutoGeneratedMipmaps,
-    )
-    .unwrap();
-    texture
+    glium::texture::Texture2d::with_mipmaps(display, image, MipmapsOption::AutoGeneratedMipmaps)
+        .unwrap()
 }
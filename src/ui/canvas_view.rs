@@ -0,0 +1,131 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::rc::Rc;
+
+use euclid::{Box2D, Point2D};
+use glium::backend::{Context, Facade};
+use glium::texture::Texture2d;
+use glium::Surface;
+use imgui::{Image, TextureId, Ui};
+use imgui_glium_renderer::Renderer;
+
+use super::UIComponent;
+use crate::canvas::{Canvas, CanvasError, CanvasObject};
+use crate::event_handling::Imgui;
+use crate::geometry::PixelSpace;
+use crate::graphics::primitives::Color;
+use crate::graphics::target::RenderTarget;
+
+/// Shows a canvas inside an ImGui window, e.g. a child window or a
+/// [`DockingLayout`](super::docking::DockingLayout) slot, filling the space
+/// left in it.
+///
+/// The canvas is rendered into a texture sized like the widget with
+/// [`CanvasView::render`], which also moves the canvas viewport onto the
+/// widget so zoom, clicks and snapping work in its coordinates. While the
+/// widget is hovered, mouse events are passed on to the canvas instead of
+/// being captured by the UI.
+pub struct CanvasView {
+    context: Rc<Context>,
+    texture: Option<(Rc<Texture2d>, TextureId)>,
+    /// Where the widget was drawn last, in window pixels.
+    rect: Option<Box2D<u32, PixelSpace>>,
+    hovered: Rc<Cell<bool>>,
+    pub background: Color,
+}
+
+impl CanvasView {
+    pub fn new(facade: &impl Facade, imgui: &mut Imgui) -> CanvasView {
+        let hovered = Rc::new(Cell::new(false));
+        imgui.pass_mouse_through(&hovered);
+        CanvasView {
+            context: facade.get_context().clone(),
+            texture: None,
+            rect: None,
+            hovered,
+            background: Color::WHITE,
+        }
+    }
+
+    /// Renders the canvas into the texture shown by the widget. Call it every
+    /// frame before the UI is drawn, the texture follows the size of the
+    /// widget in the previous frame.
+    pub fn render<T: Any + 'static>(
+        &mut self,
+        renderer: &mut Renderer,
+        canvas: &mut Canvas,
+        obj: &dyn CanvasObject,
+        generic: &T,
+    ) -> Result<(), CanvasError> {
+        let rect = match self.rect {
+            Some(rect) if !rect.is_empty() => rect,
+            _ => return Ok(()),
+        };
+        if canvas.viewport() != Some(rect) {
+            canvas.set_viewport(Some(rect));
+        }
+
+        let size = rect.size();
+        let resized = match &self.texture {
+            Some((texture, _)) => texture.dimensions() != (size.width, size.height),
+            None => true,
+        };
+        if resized {
+            let texture =
+                Rc::new(Texture2d::empty(&self.context, size.width, size.height).unwrap());
+            let id = match &self.texture {
+                Some((_, id)) => {
+                    renderer.textures().replace(*id, texture.clone());
+                    *id
+                }
+                None => renderer.textures().insert(texture.clone()),
+            };
+            self.texture = Some((texture, id));
+        }
+
+        let texture = &self.texture.as_ref().unwrap().0;
+        let mut target = RenderTarget::texture(&self.context, texture);
+        let [r, g, b, a] = self.background.to_rgba_array();
+        target.clear_color(r, g, b, a);
+        canvas.draw(target, obj, generic)
+    }
+}
+
+impl<TCb> UIComponent<TCb> for CanvasView {
+    type Model = ();
+
+    fn draw(&mut self, ui: &Ui, _model: &(), _cmd: &mut TCb) {
+        let [x, y] = ui.cursor_screen_pos();
+        let [width, height] = ui.content_region_avail();
+        let (width, height) = (width.max(0.), height.max(0.));
+        let [sx, sy] = ui.io().display_framebuffer_scale;
+        let [display_width, display_height] = ui.io().display_size;
+        // The widget can reach out of the window, the viewport can't.
+        let framebuffer = Point2D::new(display_width * sx, display_height * sy);
+        let to_pixels = |x: f32, y: f32| {
+            Point2D::new(x * sx, y * sy)
+                .round()
+                .clamp(Point2D::origin(), framebuffer)
+                .try_cast()
+        };
+        self.rect = match (to_pixels(x, y), to_pixels(x + width, y + height)) {
+            (Some(min), Some(max)) => Some(Box2D::new(min, max)),
+            _ => None,
+        };
+
+        match &self.texture {
+            Some((_, id)) => {
+                // The texture is stored bottom up.
+                Image::new(*id, [width, height])
+                    .uv0([0., 1.])
+                    .uv1([1., 0.])
+                    .build(ui);
+                self.hovered.set(ui.is_item_hovered());
+            }
+            None => {
+                ui.dummy([width, height]);
+                self.hovered.set(false);
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::marker::PhantomData;
+
+use imgui::{im_str, ImString, Selectable, StyleColor, Ui};
+
+use super::UIComponent;
+use crate::state::{CommandRequest, UndoStack};
+
+/// Receives the requests made in a [`HistoryPanel`].
+pub trait HistoryCallback {
+    fn command_request(&mut self, request: CommandRequest);
+}
+
+impl HistoryCallback for Vec<CommandRequest> {
+    fn command_request(&mut self, request: CommandRequest) {
+        self.push(request);
+    }
+}
+
+/// Undo and redo buttons above the list of entries in an [`UndoStack`].
+/// Clicking an entry goes back or forward to it, entries that can be redone
+/// are greyed out.
+pub struct HistoryPanel<S> {
+    state: PhantomData<fn(&S)>,
+}
+
+impl<S> Default for HistoryPanel<S> {
+    fn default() -> Self {
+        HistoryPanel { state: PhantomData }
+    }
+}
+
+impl<S: 'static, TCb: HistoryCallback> UIComponent<TCb> for HistoryPanel<S> {
+    type Model = UndoStack<S>;
+
+    fn draw(&mut self, ui: &Ui, history: &UndoStack<S>, cmd: &mut TCb) {
+        if ui.button(im_str!("Undo"), [0., 0.]) && history.can_undo() {
+            cmd.command_request(CommandRequest::Undo);
+        }
+        ui.same_line(0.);
+        if ui.button(im_str!("Redo"), [0., 0.]) && history.can_redo() {
+            cmd.command_request(CommandRequest::Redo);
+        }
+        ui.separator();
+
+        let position = history.position();
+        let disabled = ui.style_color(StyleColor::TextDisabled);
+        let entries = std::iter::once("Start".to_string()).chain(history.descriptions());
+        for (i, description) in entries.enumerate() {
+            let id = ui.push_id(i as i32);
+            let color = (i > position).then(|| ui.push_style_color(StyleColor::Text, disabled));
+
+            let label = ImString::new(description);
+            if Selectable::new(&label).selected(i == position).build(ui) && i != position {
+                cmd.command_request(CommandRequest::Goto(i));
+            }
+
+            if let Some(color) = color {
+                color.pop(ui);
+            }
+            id.pop(ui);
+        }
+    }
+}
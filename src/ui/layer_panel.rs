@@ -0,0 +1,67 @@
+use imgui::{im_str, Direction, Slider, Ui};
+
+use super::UIComponent;
+use crate::layers::{LayerCommand, Layers};
+
+/// Receives the changes made in a [`LayerPanel`]. The panel reads the layers
+/// while it is drawn, so collect the commands in a `Vec` and pass them to
+/// [`Layers::apply`] afterwards.
+pub trait LayerPanelCallback {
+    fn layer_command(&mut self, command: LayerCommand);
+}
+
+impl LayerPanelCallback for Vec<LayerCommand> {
+    fn layer_command(&mut self, command: LayerCommand) {
+        self.push(command);
+    }
+}
+
+/// Lists the layers from the top down with toggles for visibility and
+/// locking, an opacity slider and buttons to reorder them.
+#[derive(Default)]
+pub struct LayerPanel;
+
+impl<TCb: LayerPanelCallback> UIComponent<TCb> for LayerPanel {
+    type Model = Layers;
+
+    fn draw(&mut self, ui: &Ui, layers: &Layers, cmd: &mut TCb) {
+        let count = layers.layers().len();
+        for (i, layer) in layers.layers().iter().enumerate().rev() {
+            let id = ui.push_id(i as i32);
+
+            let mut visible = layer.visible;
+            if ui.checkbox(im_str!("##visible"), &mut visible) {
+                cmd.layer_command(LayerCommand::SetVisible(layer.id(), visible));
+            }
+            if ui.is_item_hovered() {
+                ui.tooltip_text("Visible");
+            }
+            ui.same_line(0.);
+            let mut locked = layer.locked;
+            if ui.checkbox(im_str!("##locked"), &mut locked) {
+                cmd.layer_command(LayerCommand::SetLocked(layer.id(), locked));
+            }
+            if ui.is_item_hovered() {
+                ui.tooltip_text("Locked");
+            }
+            ui.same_line(0.);
+            if ui.arrow_button(im_str!("##raise"), Direction::Up) && i + 1 < count {
+                cmd.layer_command(LayerCommand::Raise(layer.id()));
+            }
+            ui.same_line(0.);
+            if ui.arrow_button(im_str!("##lower"), Direction::Down) && i > 0 {
+                cmd.layer_command(LayerCommand::Lower(layer.id()));
+            }
+            ui.same_line(0.);
+            ui.text(&layer.name);
+
+            let mut opacity = layer.opacity;
+            if Slider::new(im_str!("Opacity"), 0. ..=1.).build(ui, &mut opacity) {
+                cmd.layer_command(LayerCommand::SetOpacity(layer.id(), opacity));
+            }
+            ui.separator();
+
+            id.pop(ui);
+        }
+    }
+}
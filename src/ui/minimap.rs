@@ -0,0 +1,198 @@
+use std::any::Any;
+use std::rc::Rc;
+
+use euclid::{Box2D, Point2D, Size2D, Transform2D};
+use glium::backend::{Context, Facade};
+use glium::texture::Texture2d;
+use glium::Surface;
+use imgui::{Image, MouseButton, TextureId, Ui};
+use imgui_glium_renderer::Renderer;
+
+use super::UIComponent;
+use crate::canvas::{canvas_bounds, Canvas, CanvasError, CanvasObject};
+use crate::geometry::{CanvasSpace, ScreenSpace};
+use crate::graphics::primitives::Color;
+use crate::graphics::target::RenderTarget;
+
+/// Smallest extent of [`Minimap::bounds`] in canvas units, so a point or a
+/// straight line still gives a finite zoom.
+const MIN_EXTENT: f32 = 1e-3;
+
+/// Receives the position the user picked on a [`Minimap`].
+pub trait MinimapCallback {
+    fn center_canvas_on(&mut self, point: Point2D<f32, CanvasSpace>);
+}
+
+/// Changes to the canvas requested by a [`Minimap`]. The minimap reads the
+/// canvas while it is drawn, so they are collected and applied afterwards.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MinimapCommand {
+    CenterOn(Point2D<f32, CanvasSpace>),
+}
+
+impl MinimapCommand {
+    pub fn apply(self, canvas: &mut Canvas) {
+        match self {
+            MinimapCommand::CenterOn(point) => canvas.center_on(point),
+        }
+    }
+}
+
+impl MinimapCallback for Vec<MinimapCommand> {
+    fn center_canvas_on(&mut self, point: Point2D<f32, CanvasSpace>) {
+        self.push(MinimapCommand::CenterOn(point));
+    }
+}
+
+/// Overview of a fixed part of the canvas with the visible area marked on it.
+/// Clicking or dragging on it centers the canvas on that point.
+///
+/// The content is rendered offscreen with [`Minimap::render`], call it before
+/// the UI is drawn whenever the scene changed.
+pub struct Minimap {
+    context: Rc<Context>,
+    texture: Rc<Texture2d>,
+    texture_id: TextureId,
+    /// Part of the canvas shown, it is fit into the minimap keeping its
+    /// aspect ratio.
+    pub bounds: Box2D<f32, CanvasSpace>,
+    pub background: Color,
+    pub viewport_color: Color,
+    dragging: bool,
+}
+
+impl Minimap {
+    pub fn new(
+        facade: &impl Facade,
+        renderer: &mut Renderer,
+        (width, height): (u32, u32),
+        bounds: Box2D<f32, CanvasSpace>,
+    ) -> Minimap {
+        let texture = Rc::new(Texture2d::empty(facade, width, height).unwrap());
+        let texture_id = renderer.textures().insert(texture.clone());
+        Minimap {
+            context: facade.get_context().clone(),
+            texture,
+            texture_id,
+            bounds,
+            background: Color::WHITE_SMOKE,
+            viewport_color: Color::ORANGE_RED,
+            dragging: false,
+        }
+    }
+
+    /// Shows everything `obj` draws, if it knows its bounds.
+    pub fn fit(&mut self, obj: &dyn CanvasObject) {
+        if let Some(bounds) = canvas_bounds(obj, &Transform2D::identity()) {
+            self.bounds = bounds;
+        }
+    }
+
+    pub fn view_transform(&self) -> Transform2D<f32, CanvasSpace, ScreenSpace> {
+        fit_transform(self.bounds, self.texture.dimensions())
+    }
+
+    pub fn render<T: Any + 'static>(
+        &self,
+        canvas: &Canvas,
+        obj: &dyn CanvasObject,
+        generic: &T,
+    ) -> Result<(), CanvasError> {
+        let mut target = RenderTarget::texture(&self.context, &self.texture);
+        let [r, g, b, a] = self.background.to_rgba_array();
+        target.clear_color(r, g, b, a);
+        canvas.draw_with_view_transform(target, self.view_transform(), obj, generic)
+    }
+}
+
+impl<TCb: MinimapCallback> UIComponent<TCb> for Minimap {
+    type Model = Canvas;
+
+    fn draw(&mut self, ui: &Ui, canvas: &Canvas, cmd: &mut TCb) {
+        let (width, height) = self.texture.dimensions();
+        let size = [width as f32, height as f32];
+        let [x, y] = ui.cursor_screen_pos();
+
+        // The texture is stored bottom up.
+        Image::new(self.texture_id, size)
+            .uv0([0., 1.])
+            .uv1([1., 0.])
+            .build(ui);
+
+        let view_transform = self.view_transform();
+        let to_ui = |p: Point2D<f32, ScreenSpace>| {
+            let p = view_transform.transform_point(
+                canvas
+                    .view_transform()
+                    .inverse()
+                    .unwrap()
+                    .transform_point(p),
+            );
+            [x + (p.x + 1.) / 2. * size[0], y + (1. - p.y) / 2. * size[1]]
+        };
+        let draw_list = ui.get_window_draw_list();
+        draw_list.with_clip_rect_intersect([x, y], [x + size[0], y + size[1]], || {
+            draw_list
+                .add_rect(
+                    to_ui(Point2D::new(-1., 1.)),
+                    to_ui(Point2D::new(1., -1.)),
+                    self.viewport_color.to_rgba_array(),
+                )
+                .thickness(2.)
+                .build();
+        });
+
+        if ui.is_item_hovered() && ui.is_mouse_clicked(MouseButton::Left) {
+            self.dragging = true;
+        }
+        if !ui.is_mouse_down(MouseButton::Left) {
+            self.dragging = false;
+        }
+        if self.dragging {
+            let [mx, my] = ui.io().mouse_pos;
+            let screen = Point2D::new((mx - x) / size[0] * 2. - 1., 1. - (my - y) / size[1] * 2.);
+            let point = view_transform.inverse().unwrap().transform_point(screen);
+            cmd.center_canvas_on(point);
+        }
+    }
+}
+
+/// Shows `bounds` as large as possible on a texture of the given size,
+/// keeping the aspect ratio.
+fn fit_transform(
+    bounds: Box2D<f32, CanvasSpace>,
+    (width, height): (u32, u32),
+) -> Transform2D<f32, CanvasSpace, ScreenSpace> {
+    let size = bounds.size().max(Size2D::new(MIN_EXTENT, MIN_EXTENT));
+    let pixels_per_unit = (width as f32 / size.width).min(height as f32 / size.height);
+    let center = bounds.center();
+
+    Transform2D::translation(-center.x, -center.y).then_scale(
+        pixels_per_unit * 2. / width as f32,
+        pixels_per_unit * 2. / height as f32,
+    )
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    #[test]
+    fn it_fits_bounds_without_extent() {
+        let line = Box2D::new(Point2D::new(0., 1.), Point2D::new(10., 1.));
+        let transform = fit_transform(line, (200, 100));
+        assert_eq!(
+            transform.transform_point(Point2D::new(10., 1.)),
+            Point2D::new(1., 0.)
+        );
+
+        let point = Box2D::new(Point2D::new(3., 4.), Point2D::new(3., 4.));
+        let transform = fit_transform(point, (200, 100));
+        assert!(transform.to_array().iter().all(|v| v.is_finite()));
+        assert_eq!(
+            transform.transform_point(Point2D::new(3., 4.)),
+            Point2D::origin()
+        );
+    }
+}
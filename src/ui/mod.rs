@@ -1,6 +1,11 @@
 use imgui::Ui;
 
+pub mod canvas_view;
 pub mod docking;
+pub mod history_panel;
+pub mod layer_panel;
+pub mod minimap;
+pub mod ruler;
 
 pub trait UIComponent<TCb> {
     type Model;
@@ -0,0 +1,140 @@
+use euclid::Point2D;
+use imgui::Ui;
+
+use super::UIComponent;
+use crate::canvas::Canvas;
+use crate::graphics::primitives::Color;
+use crate::grid::{ui_rect, GridSteps};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RulerOrientation {
+    /// Shows x coordinates, dock it at the top or bottom.
+    Horizontal,
+    /// Shows y coordinates, dock it at the left or right.
+    Vertical,
+}
+
+/// Ruler showing the canvas coordinates along one edge of the screen, with a
+/// marker at the cursor. It fills the window it is drawn in, so give it its
+/// own [`DockingLayout`](super::docking::DockingLayout) slot, about 20 pixels
+/// wide, with the canvas as its model.
+pub struct Ruler {
+    pub orientation: RulerOrientation,
+    pub steps: GridSteps,
+    /// Minimum distance between two ticks in pixels.
+    pub min_tick_spacing: f32,
+    pub background: Color,
+    pub color: Color,
+    pub marker_color: Color,
+}
+
+impl Ruler {
+    pub fn new(orientation: RulerOrientation) -> Ruler {
+        Ruler {
+            orientation,
+            steps: GridSteps::PowersOfTen,
+            min_tick_spacing: 6.,
+            background: Color::WHITE_SMOKE,
+            color: Color::DIM_GRAY,
+            marker_color: Color::ORANGE_RED,
+        }
+    }
+}
+
+impl<TCb> UIComponent<TCb> for Ruler {
+    type Model = Canvas;
+
+    fn draw(&mut self, ui: &Ui, canvas: &Canvas, _cmd: &mut TCb) {
+        let view_transform = canvas.view_transform();
+        let screen_to_canvas = view_transform.inverse().unwrap();
+        // The part of the display the canvas covers, in ui coordinates.
+        let [left, top, canvas_width, canvas_height] = ui_rect(ui, canvas);
+        let horizontal = self.orientation == RulerOrientation::Horizontal;
+
+        // Everything is computed along the ruler, `along` maps canvas
+        // coordinates to ui coordinates and `from_ui` goes back.
+        let along = |p: Point2D<f32, _>| {
+            let p = view_transform.transform_point(p);
+            if horizontal {
+                left + (p.x + 1.) / 2. * canvas_width
+            } else {
+                top + (1. - p.y) / 2. * canvas_height
+            }
+        };
+        let from_ui = |u: f32| {
+            if horizontal {
+                screen_to_canvas
+                    .transform_point(Point2D::new((u - left) / canvas_width * 2. - 1., 0.))
+                    .x
+            } else {
+                screen_to_canvas
+                    .transform_point(Point2D::new(0., 1. - (u - top) / canvas_height * 2.))
+                    .y
+            }
+        };
+        let pixels_per_unit = if horizontal {
+            view_transform.m11 * canvas_width / 2.
+        } else {
+            view_transform.m22 * canvas_height / 2.
+        };
+
+        let spacing = self.steps.spacing(self.min_tick_spacing, pixels_per_unit);
+        let decimals = self.steps.decimals(spacing.major);
+        let subdivisions = (spacing.major / spacing.minor).round() as i64;
+
+        let [x, y] = ui.window_pos();
+        let [width, height] = ui.window_size();
+        let (start, length, depth) = if horizontal {
+            (x, width, height)
+        } else {
+            (y, height, width)
+        };
+        // Position of a point `a` along and `d` across the ruler.
+        let at = |a: f32, d: f32| if horizontal { [a, y + d] } else { [x + d, a] };
+
+        let draw_list = ui.get_window_draw_list();
+        draw_list.with_clip_rect([x, y], [x + width, y + height], || {
+            draw_list
+                .add_rect(
+                    [x, y],
+                    [x + width, y + height],
+                    self.background.to_rgba_array(),
+                )
+                .filled(true)
+                .build();
+
+            let color = self.color.to_rgba_array();
+            let minor_color = self.color.with_alpha(spacing.minor_alpha).to_rgba_array();
+            let first = (from_ui(start) / spacing.minor).floor() as i64;
+            let last = (from_ui(start + length) / spacing.minor).ceil() as i64;
+            for i in first.min(last)..=first.max(last) {
+                let value = i as f32 * spacing.minor;
+                let a = along(Point2D::new(value, value));
+                if i % subdivisions == 0 {
+                    draw_list.add_line(at(a, 0.), at(a, depth), color).build();
+                    let text = format!("{:.*}", decimals, value);
+                    draw_list.add_text(at(a + 2., 0.), color, text);
+                } else {
+                    let tick = if i % (subdivisions / 2).max(1) == 0 {
+                        0.5
+                    } else {
+                        0.25
+                    };
+                    draw_list
+                        .add_line(at(a, depth * (1. - tick)), at(a, depth), minor_color)
+                        .build();
+                }
+            }
+
+            let cursor = along(canvas.cursor_position());
+            draw_list
+                .add_line(
+                    at(cursor, 0.),
+                    at(cursor, depth),
+                    self.marker_color.to_rgba_array(),
+                )
+                .thickness(2.)
+                .build();
+        });
+    }
+}